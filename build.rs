@@ -11,11 +11,20 @@ const LUCIDE_ICONS: &[&str] = &[
 	"calendar",
 	"network",
 	"wifi",
+	"wifi-high",
+	"wifi-low",
+	"wifi-zero",
 	"wifi-off",
+	"lock",
+	"refresh-cw",
 	"bluetooth",
 	"bluetooth-connected",
 	"bluetooth-off",
 	"bluetooth-searching",
+	"battery-full",
+	"battery-medium",
+	"battery-low",
+	"battery-warning",
 	"arrow-up-down",
 	"file-terminal",
 	"circle-x",
@@ -28,10 +37,13 @@ const LUCIDE_ICONS: &[&str] = &[
 	"pipette",
 	"settings",
 	"camera",
+	"video",
+	"screen-share",
 	"circle",
 	"bell",
 	"mic",
 	"mic-off",
+	"mic-vocal",
 	"folder",
 	"folder-down",
 	"folder-code",
@@ -43,8 +55,12 @@ const LUCIDE_ICONS: &[&str] = &[
 	"pause",
 	"skip-forward",
 	"repeat",
+	"repeat-1",
 	"headphones",
 	"headset",
+	"speaker",
+	"monitor-speaker",
+	"chevron-down",
 	"chevron-left",
 	"chevron-right",
 	"cloud",
@@ -137,7 +153,7 @@ fn main() {
 	glib_build_tools::compile_resources(paths, xml_path.to_str().unwrap(), "lucide.gresource");
 
 	let code = format!(
-		"#[derive(Debug, Clone, Copy)]\npub enum Icon {{\n{}\n}}\nimpl Icon {{\n    pub fn name(&self) -> &'static \
+		"#[derive(Debug, Clone, Copy, PartialEq, Eq)]\npub enum Icon {{\n{}\n}}\nimpl Icon {{\n    pub fn name(&self) -> &'static \
 		 str {{\n        match self {{\n{}\n        }}\n    }}\n}}",
 		variants, matches
 	);
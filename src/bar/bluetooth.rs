@@ -1,18 +1,22 @@
-use astal_bluetooth::Bluetooth as Bt;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
 use astal_bluetooth::prelude::{BluetoothExt, DeviceExt};
+use astal_bluetooth::{Bluetooth as Bt, Device};
 use glib::clone;
 use gtk4::prelude::*;
-use gtk4::{ClosureExpression, PropertyExpression, glib};
+use gtk4::{ClosureExpression, PropertyExpression, gio, glib};
 
 use crate::icons;
-use crate::popups::bluetooth::BluetoothPopup;
+use crate::popups::bluetooth::{BluetoothPopup, battery_icon, battery_level};
 
 pub struct Bluetooth {
 	widget: gtk4::Button,
 }
 
 impl Bluetooth {
-	pub fn new() -> Self {
+	pub fn new(args: &crate::Args) -> Self {
 		let bt = Bt::default();
 
 		let button_box = gtk4::Box::builder()
@@ -24,8 +28,16 @@ impl Bluetooth {
 		let default_label = format!("{}", bt.devices().into_iter().filter(|d| d.is_connected()).count());
 		let label = gtk4::Label::builder().label(default_label).build();
 
+		let battery_image = gtk4::Image::builder().visible(false).build();
+		let battery_label = gtk4::Label::builder()
+			.css_classes(["bluetooth-battery"])
+			.visible(false)
+			.build();
+
 		button_box.append(&icon);
 		button_box.append(&label);
+		button_box.append(&battery_image);
+		button_box.append(&battery_label);
 
 		let button = gtk4::Button::builder()
 			.child(&button_box)
@@ -40,8 +52,7 @@ impl Bluetooth {
 			glib::closure!(
 				|_: Option<glib::Object>, is_powered: bool, is_connected: bool| -> glib::GString {
 					if is_connected {
-						// TODO get proper connected icon
-						icons::Icon::Bluetooth.name().into()
+						icons::Icon::BluetoothConnected.name().into()
 					} else if is_powered {
 						icons::Icon::Bluetooth.name().into()
 					} else {
@@ -62,6 +73,83 @@ impl Bluetooth {
 			}
 		));
 
+		// Lowest battery among connected devices, plus a one-off notification per device when it runs low
+		let threshold = args.bluetooth_battery_warning;
+		let warned = Rc::new(RefCell::new(HashSet::<String>::new()));
+		let update_battery = clone!(
+			#[weak]
+			button,
+			#[weak]
+			battery_image,
+			#[weak]
+			battery_label,
+			move || {
+				let levels = Bt::default()
+					.devices()
+					.into_iter()
+					.filter(|device| device.is_connected())
+					.filter_map(|device| battery_level(device.battery_percentage()).map(|level| (device, level)))
+					.collect::<Vec<_>>();
+
+				let mut warned = warned.borrow_mut();
+				warned.retain(|address| {
+					levels
+						.iter()
+						.any(|(device, level)| device.address() == *address && *level < threshold)
+				});
+				for (device, level) in &levels {
+					if *level < threshold && warned.insert(device.address().to_string()) {
+						notify_low_battery(device, *level);
+					}
+				}
+
+				let lowest = levels.iter().map(|(_, level)| *level).min();
+				let low = lowest.is_some_and(|level| level < threshold);
+				if low {
+					button.add_css_class("low-battery");
+				} else {
+					button.remove_css_class("low-battery");
+				}
+
+				battery_image.set_visible(lowest.is_some());
+				battery_label.set_visible(lowest.is_some());
+				if let Some(level) = lowest {
+					battery_image.set_icon_name(Some(battery_icon(level, low).name()));
+					battery_label.set_label(&format!("{}%", level));
+				}
+			}
+		);
+
+		let watch_device = clone!(
+			#[strong]
+			update_battery,
+			move |device: &Device| {
+				for property in ["battery-percentage", "connected"] {
+					device.connect_notify_local(
+						Some(property),
+						clone!(
+							#[strong]
+							update_battery,
+							move |_, _| update_battery()
+						),
+					);
+				}
+			}
+		);
+		for device in bt.devices() {
+			watch_device(&device);
+		}
+		bt.connect_device_added(clone!(
+			#[strong]
+			update_battery,
+			move |_, device| {
+				watch_device(device);
+				update_battery();
+			}
+		));
+		update_battery();
+		bt.connect_device_removed(move |_, _| update_battery());
+
 		let popup = BluetoothPopup::new();
 		popup.set_parent(&button);
 
@@ -80,3 +168,14 @@ impl Bluetooth {
 		&self.widget
 	}
 }
+
+fn notify_low_battery(device: &Device, level: u8) {
+	let Some(app) = gio::Application::default() else {
+		return;
+	};
+
+	let notification = gio::Notification::new("Low battery");
+	notification.set_body(Some(&format!("{} is at {}%", device.alias(), level)));
+	notification.set_priority(gio::NotificationPriority::High);
+	app.send_notification(Some(&format!("bluetooth-battery-{}", device.address())), &notification);
+}
@@ -0,0 +1,227 @@
+use std::cell::{Cell, RefCell};
+use std::ffi::OsStr;
+use std::sync::OnceLock;
+
+use astal_wp::Wp;
+use astal_wp::prelude::NodeExt;
+use glib::clone;
+use glib::subclass::Signal;
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use gtk4::{gio, glib};
+
+use super::spectrum::{self, FFT_SIZE};
+use crate::popups::volume::default_endpoint;
+
+/// `node.name` of our `pw-record` stream, so it can be told apart from real recorders
+pub const CAPTURE_NODE_NAME: &str = "niribar-visualizer";
+
+pub const BAR_COUNT: usize = 12;
+const SAMPLE_RATE: u32 = 48_000;
+/// How much a bar may drop per audio chunk, gives the cava-like falloff
+const FALLOFF: f32 = 0.05;
+
+glib::wrapper! {
+	/// One capture of the default sink's monitor shared by every visualizer, so multiple bars don't
+	/// each run their own `pw-record` and FFT.
+	pub struct AudioCapture(ObjectSubclass<imp::AudioCapture>);
+}
+
+impl AudioCapture {
+	pub fn default() -> Self {
+		thread_local! {
+			static CAPTURE: AudioCapture = glib::Object::builder().build();
+		}
+		CAPTURE.with(Clone::clone)
+	}
+
+	/// Starts capturing for a new subscriber, pair with [`Self::release`].
+	pub fn acquire(&self) {
+		let imp = self.imp();
+		imp.subscribers.set(imp.subscribers.get() + 1);
+		if imp.subscribers.get() == 1 {
+			imp.start_capture();
+		}
+	}
+
+	pub fn release(&self) {
+		let imp = self.imp();
+		imp.subscribers.set(imp.subscribers.get().saturating_sub(1));
+		if imp.subscribers.get() == 0 {
+			imp.stop_capture();
+		}
+	}
+
+	pub fn connect_levels<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+		self.connect_local("levels", false, move |values| {
+			let obj = values[0].get::<Self>().expect("levels emitted by a non-AudioCapture");
+			f(&obj);
+			None
+		})
+	}
+
+	/// Current bar levels in `0.0..=1.0`, updated before every `levels` signal
+	pub fn bars(&self) -> Vec<f32> {
+		self.imp().bars.borrow().clone()
+	}
+}
+
+mod imp {
+	use super::*;
+
+	/// A running `pw-record` capturing the default sink's monitor
+	struct Capture {
+		process: gio::Subprocess,
+		reader:  glib::JoinHandle<()>,
+	}
+
+	#[derive(Default)]
+	pub struct AudioCapture {
+		pub(super) subscribers: Cell<usize>,
+		pub(super) bars:        RefCell<Vec<f32>>,
+		samples:                RefCell<Vec<f32>>,
+		capture:                RefCell<Option<Capture>>,
+	}
+
+	#[glib::object_subclass]
+	impl ObjectSubclass for AudioCapture {
+		type Type = super::AudioCapture;
+
+		const NAME: &'static str = "AudioCapture";
+	}
+
+	impl ObjectImpl for AudioCapture {
+		fn constructed(&self) {
+			self.parent_constructed();
+
+			self.bars.replace(vec![0.0; BAR_COUNT]);
+
+			let obj = self.obj();
+			Wp::default().audio().connect_notify_local(
+				Some("default-speaker"),
+				clone!(
+					#[weak]
+					obj,
+					move |_, _| {
+						// Follow the new default sink, or start once there is one
+						let imp = obj.imp();
+						if imp.subscribers.get() > 0 {
+							imp.stop_capture();
+							imp.start_capture();
+						}
+					}
+				),
+			);
+		}
+
+		fn signals() -> &'static [Signal] {
+			static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+			SIGNALS.get_or_init(|| vec![Signal::builder("levels").build()])
+		}
+
+		fn dispose(&self) {
+			self.stop_capture();
+		}
+	}
+
+	impl AudioCapture {
+		pub(super) fn start_capture(&self) {
+			let Some(speaker) = default_endpoint(&Wp::default().audio(), "default-speaker") else {
+				return;
+			};
+			let target = speaker.serial().to_string();
+			let rate = SAMPLE_RATE.to_string();
+			let properties = format!(
+				"{{ stream.capture.sink = true node.passive = true node.name = {} }}",
+				CAPTURE_NODE_NAME
+			);
+			let argv = [
+				"pw-record",
+				"--target",
+				target.as_str(),
+				// Record the sink's monitor without keeping it from suspending
+				"-P",
+				properties.as_str(),
+				"--rate",
+				rate.as_str(),
+				"--channels",
+				"1",
+				"--format",
+				"f32",
+				"--raw",
+				"-",
+			]
+			.map(OsStr::new);
+
+			let process = match gio::Subprocess::newv(
+				&argv,
+				gio::SubprocessFlags::STDOUT_PIPE | gio::SubprocessFlags::STDERR_SILENCE,
+			) {
+				Ok(process) => process,
+				Err(e) => {
+					eprintln!("Failed to start pw-record for the visualizer: {}", e);
+					return;
+				}
+			};
+
+			let Some(stdout) = process.stdout_pipe() else {
+				process.force_exit();
+				return;
+			};
+
+			let obj = self.obj();
+			let reader = glib::spawn_future_local(clone!(
+				#[weak]
+				obj,
+				async move {
+					let mut remainder = Vec::new();
+					loop {
+						let bytes = match stdout.read_bytes_future(4 * FFT_SIZE, glib::Priority::DEFAULT).await {
+							Ok(bytes) if !bytes.is_empty() => bytes,
+							_ => break,
+						};
+
+						remainder.extend_from_slice(&bytes);
+						let whole = remainder.len() - remainder.len() % 4;
+						let samples = remainder[..whole]
+							.chunks_exact(4)
+							.map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+						obj.imp().push_samples(samples);
+						remainder.drain(..whole);
+					}
+				}
+			));
+
+			self.capture.replace(Some(Capture { process, reader }));
+		}
+
+		pub(super) fn stop_capture(&self) {
+			if let Some(capture) = self.capture.take() {
+				capture.reader.abort();
+				capture.process.force_exit();
+			}
+
+			self.samples.borrow_mut().clear();
+			self.bars.replace(vec![0.0; BAR_COUNT]);
+			self.obj().emit_by_name::<()>("levels", &[]);
+		}
+
+		fn push_samples(&self, new_samples: impl Iterator<Item = f32>) {
+			{
+				let mut samples = self.samples.borrow_mut();
+				samples.extend(new_samples);
+				if samples.len() > FFT_SIZE {
+					let excess = samples.len() - FFT_SIZE;
+					samples.drain(..excess);
+				}
+
+				let levels = spectrum::bars(&samples, SAMPLE_RATE as f32, BAR_COUNT);
+				for (bar, level) in self.bars.borrow_mut().iter_mut().zip(levels) {
+					*bar = level.max(*bar - FALLOFF);
+				}
+			}
+
+			self.obj().emit_by_name::<()>("levels", &[]);
+		}
+	}
+}
@@ -1,5 +1,8 @@
 mod button_state;
+pub mod capture;
 mod single_media_player;
+mod spectrum;
+mod visualizer;
 mod widget;
 
 pub use widget::MediaPlayerWidget;
@@ -10,8 +10,15 @@ use gtk4::{CompositeTemplate, EventControllerMotion, glib};
 use lazy_regex::regex;
 
 use super::button_state::ButtonState;
+use super::visualizer::Visualizer;
 use crate::icons;
 
+/// Volume change per scroll step on the bar widget
+const VOLUME_SCROLL_STEP: f64 = 0.05;
+/// Side buttons as reported by GDK (evdev `BTN_SIDE`/`BTN_EXTRA`)
+const MOUSE_BUTTON_BACK: u32 = 8;
+const MOUSE_BUTTON_FORWARD: u32 = 9;
+
 glib::wrapper! {
 	pub struct SingleMediaPlayerWidget(ObjectSubclass<imp::SingleMediaPlayerWidget>)
 		@extends gtk4::Box, gtk4::Widget,
@@ -109,6 +116,7 @@ mod imp {
 			let popup = MediaPlayerPopup::new(&self.player.borrow());
 			popup.set_parent(widget);
 			popup.set_autohide(false);
+			obj.add_css_class(&popup.palette_class());
 
 			let hover_count = Rc::new(Cell::new(0u32));
 			let popdown_timeout: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
@@ -208,6 +216,48 @@ mod imp {
 			));
 			popup.add_controller(popup_hover_controller);
 
+			let scroll_controller = gtk4::EventControllerScroll::new(gtk4::EventControllerScrollFlags::VERTICAL);
+			scroll_controller.connect_scroll(clone!(
+				#[weak]
+				obj,
+				#[upgrade_or]
+				glib::Propagation::Proceed,
+				move |_, _, dy| {
+					let player = obj.player();
+					let volume = player.volume();
+					// Players that don't expose MPRIS `Volume` report a negative value
+					if volume < 0.0 || dy == 0.0 {
+						return glib::Propagation::Proceed;
+					}
+
+					let new_volume = (volume - dy * VOLUME_SCROLL_STEP).clamp(0.0, 1.0);
+					player.set_volume(new_volume);
+					glib::Propagation::Stop
+				}
+			));
+			self.player_button.add_controller(scroll_controller);
+
+			// Button 0 listens to every mouse button, so we can dispatch middle/back/forward ourselves
+			let click_controller = gtk4::GestureClick::builder().button(0).build();
+			click_controller.connect_pressed(clone!(
+				#[weak]
+				obj,
+				move |gesture, _, _, _| {
+					let player = obj.player();
+					match gesture.current_button() {
+						gtk4::gdk::BUTTON_MIDDLE | MOUSE_BUTTON_FORWARD if player.can_go_next() => {
+							player.next();
+						}
+						MOUSE_BUTTON_BACK if player.can_go_previous() => {
+							player.previous();
+						}
+						_ => return,
+					}
+					gesture.set_state(gtk4::EventSequenceState::Claimed);
+				}
+			));
+			self.player_button.add_controller(click_controller);
+
 			let player = self.player.borrow();
 			let title_label = &*self.title_label;
 			player
@@ -220,6 +270,14 @@ mod imp {
 				.transform_to(|_, status: PlaybackStatus| Some(status == PlaybackStatus::Playing))
 				.sync_create()
 				.build();
+
+			let visualizer = Visualizer::new();
+			player
+				.bind_property("playback-status", &visualizer, "playing")
+				.transform_to(|_, status: PlaybackStatus| Some(status == PlaybackStatus::Playing))
+				.sync_create()
+				.build();
+			obj.append(&visualizer);
 		}
 
 		fn signals() -> &'static [Signal] {
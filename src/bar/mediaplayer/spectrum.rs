@@ -0,0 +1,88 @@
+use std::f32::consts::PI;
+
+/// Number of samples per analysis window, must be a power of two
+pub const FFT_SIZE: usize = 1024;
+
+/// Lowest and highest frequencies shown by the bars, in Hz
+const MIN_FREQUENCY: f32 = 50.0;
+const MAX_FREQUENCY: f32 = 12_000.0;
+
+/// Levels below this (in dBFS) are drawn as empty bars
+const FLOOR_DB: f32 = -60.0;
+
+/// Splits the last [`FFT_SIZE`] mono samples into `bar_count` logarithmically spaced frequency bands
+/// and returns their levels normalized to `0.0..=1.0`.
+pub fn bars(samples: &[f32], sample_rate: f32, bar_count: usize) -> Vec<f32> {
+	if samples.len() < FFT_SIZE || bar_count == 0 {
+		return vec![0.0; bar_count];
+	}
+
+	let window = &samples[samples.len() - FFT_SIZE..];
+	let mut bins = window
+		.iter()
+		.enumerate()
+		.map(|(i, sample)| {
+			// Hann window to keep spectral leakage down
+			let hann = 0.5 - 0.5 * (2.0 * PI * i as f32 / (FFT_SIZE - 1) as f32).cos();
+			(sample * hann, 0.0)
+		})
+		.collect::<Vec<_>>();
+	fft(&mut bins);
+
+	let bin_width = sample_rate / FFT_SIZE as f32;
+	let ratio = (MAX_FREQUENCY / MIN_FREQUENCY).powf(1.0 / bar_count as f32);
+
+	(0..bar_count)
+		.map(|bar| {
+			let low = MIN_FREQUENCY * ratio.powi(bar as i32);
+			let high = low * ratio;
+			let first = ((low / bin_width) as usize).max(1);
+			let last = ((high / bin_width) as usize).clamp(first + 1, FFT_SIZE / 2);
+
+			let peak = bins[first..last]
+				.iter()
+				.map(|(re, im)| (re * re + im * im).sqrt())
+				.fold(0.0, f32::max);
+
+			// A full scale sine ends up at roughly FFT_SIZE / 4 after the Hann window
+			let db = 20.0 * (peak / (FFT_SIZE as f32 / 4.0)).max(1e-9).log10();
+			((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+		})
+		.collect()
+}
+
+/// In-place iterative radix-2 Cooley-Tukey FFT over `(re, im)` pairs.
+fn fft(data: &mut [(f32, f32)]) {
+	let n = data.len();
+	debug_assert!(n.is_power_of_two());
+
+	let mut j = 0;
+	for i in 1..n {
+		let mut bit = n >> 1;
+		while j & bit != 0 {
+			j ^= bit;
+			bit >>= 1;
+		}
+		j |= bit;
+		if i < j {
+			data.swap(i, j);
+		}
+	}
+
+	let mut len = 2;
+	while len <= n {
+		let angle = -2.0 * PI / len as f32;
+		for start in (0..n).step_by(len) {
+			for k in 0..len / 2 {
+				let (w_re, w_im) = ((angle * k as f32).cos(), (angle * k as f32).sin());
+				let (a_re, a_im) = data[start + k];
+				let (b_re, b_im) = data[start + k + len / 2];
+				let (t_re, t_im) = (b_re * w_re - b_im * w_im, b_re * w_im + b_im * w_re);
+
+				data[start + k] = (a_re + t_re, a_im + t_im);
+				data[start + k + len / 2] = (a_re - t_re, a_im - t_im);
+			}
+		}
+		len <<= 1;
+	}
+}
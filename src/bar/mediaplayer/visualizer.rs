@@ -0,0 +1,138 @@
+use std::cell::{Cell, RefCell};
+
+use glib::Properties;
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use gtk4::{glib, graphene};
+
+use super::capture::{AudioCapture, BAR_COUNT};
+
+const BAR_WIDTH: i32 = 3;
+const BAR_SPACING: i32 = 2;
+const HEIGHT: i32 = 16;
+
+glib::wrapper! {
+	pub struct Visualizer(ObjectSubclass<imp::Visualizer>)
+		@extends gtk4::Widget,
+		@implements gtk4::Accessible, gtk4::Buildable, gtk4::ConstraintTarget;
+}
+
+impl Visualizer {
+	pub fn new() -> Self {
+		glib::Object::builder().build()
+	}
+}
+
+mod imp {
+	use super::*;
+
+	#[derive(Default, Properties)]
+	#[properties(wrapper_type = super::Visualizer)]
+	pub struct Visualizer {
+		/// Capturing only happens while this is set and the widget is mapped
+		#[property(get, set)]
+		playing: Cell<bool>,
+
+		/// Subscribed to the shared capture, with the handler redrawing us
+		subscription: RefCell<Option<glib::SignalHandlerId>>,
+	}
+
+	#[glib::object_subclass]
+	impl ObjectSubclass for Visualizer {
+		type ParentType = gtk4::Widget;
+		type Type = super::Visualizer;
+
+		const NAME: &'static str = "Visualizer";
+
+		fn class_init(klass: &mut Self::Class) {
+			klass.set_css_name("visualizer");
+		}
+	}
+
+	#[glib::derived_properties]
+	impl ObjectImpl for Visualizer {
+		fn constructed(&self) {
+			self.parent_constructed();
+
+			let obj = self.obj();
+			obj.set_valign(gtk4::Align::Center);
+			obj.connect_playing_notify(|obj| obj.imp().update_subscription());
+		}
+
+		fn dispose(&self) {
+			self.unsubscribe();
+		}
+	}
+
+	impl WidgetImpl for Visualizer {
+		fn map(&self) {
+			self.parent_map();
+			self.update_subscription();
+		}
+
+		fn unmap(&self) {
+			self.parent_unmap();
+			self.update_subscription();
+		}
+
+		fn measure(&self, orientation: gtk4::Orientation, _for_size: i32) -> (i32, i32, i32, i32) {
+			let size = match orientation {
+				gtk4::Orientation::Horizontal => BAR_COUNT as i32 * (BAR_WIDTH + BAR_SPACING) - BAR_SPACING,
+				_ => HEIGHT,
+			};
+			(size, size, -1, -1)
+		}
+
+		fn snapshot(&self, snapshot: &gtk4::Snapshot) {
+			let obj = self.obj();
+			let height = obj.height() as f32;
+			let color = obj.color();
+
+			let bars = match *self.subscription.borrow() {
+				Some(_) => AudioCapture::default().bars(),
+				None => vec![0.0; BAR_COUNT],
+			};
+			for (index, level) in bars.iter().enumerate() {
+				let bar_height = (level * height).max(1.0);
+				let x = (index as i32 * (BAR_WIDTH + BAR_SPACING)) as f32;
+				let rect = graphene::Rect::new(x, height - bar_height, BAR_WIDTH as f32, bar_height);
+				snapshot.append_color(&color, &rect);
+			}
+		}
+	}
+
+	impl Visualizer {
+		fn update_subscription(&self) {
+			let obj = self.obj();
+			let should_run = obj.is_playing() && obj.is_mapped();
+			let is_running = self.subscription.borrow().is_some();
+
+			match (should_run, is_running) {
+				(true, false) => self.subscribe(),
+				(false, true) => self.unsubscribe(),
+				_ => (),
+			}
+		}
+
+		fn subscribe(&self) {
+			let capture = AudioCapture::default();
+			let obj = self.obj().downgrade();
+			let handler = capture.connect_levels(move |_| {
+				if let Some(obj) = obj.upgrade() {
+					obj.queue_draw();
+				}
+			});
+			self.subscription.replace(Some(handler));
+			capture.acquire();
+		}
+
+		fn unsubscribe(&self) {
+			if let Some(handler) = self.subscription.take() {
+				let capture = AudioCapture::default();
+				capture.disconnect(handler);
+				capture.release();
+			}
+			self.obj().queue_draw();
+		}
+	}
+}
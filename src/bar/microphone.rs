@@ -0,0 +1,96 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use astal_wp::{Audio, Wp};
+use glib::clone;
+use glib::object::ObjectExt;
+use gtk4::prelude::*;
+
+use super::volume::add_volume_controls;
+use crate::icons;
+use crate::popups::volume::{VolumePopup, default_endpoint};
+
+pub struct Microphone {
+	widget: gtk4::Button,
+}
+
+impl Microphone {
+	pub fn new(args: &crate::Args) -> Self {
+		let button_box = gtk4::Box::builder()
+			.name("wireplumber")
+			.css_classes(["microphone"])
+			.orientation(gtk4::Orientation::Horizontal)
+			.spacing(4)
+			.build();
+
+		let mic_icon = gtk4::Image::from_icon_name(icons::Icon::Mic.name());
+		mic_icon.set_pixel_size(24);
+		let label = gtk4::Label::builder().label("0%").build();
+
+		button_box.append(&mic_icon);
+		button_box.append(&label);
+
+		let widget = gtk4::Button::builder().child(&button_box).build();
+
+		let popup = VolumePopup::new();
+		popup.set_parent(&widget);
+
+		widget.connect_clicked(clone!(
+			#[weak]
+			popup,
+			move |_| {
+				popup.popup();
+			}
+		));
+
+		add_volume_controls(&widget, args, "default-microphone");
+
+		let bindings = Rc::new(RefCell::new(Vec::<glib::Binding>::new()));
+
+		let changed_default_mic = clone!(
+			#[weak]
+			label,
+			#[weak]
+			mic_icon,
+			move |audio: &Audio| {
+				for binding in bindings.borrow_mut().drain(..) {
+					binding.unbind();
+				}
+
+				let Some(mic) = default_endpoint(audio, "default-microphone") else {
+					label.set_label("0%");
+					mic_icon.set_icon_name(Some(icons::Icon::MicOff.name()));
+					return;
+				};
+
+				let label_binding = mic
+					.bind_property("volume", &label, "label")
+					.transform_to(|_, volume: f64| Some(format!("{:.0}%", volume * 100.0)))
+					.sync_create()
+					.build();
+				let icon_binding = mic
+					.bind_property("mute", &mic_icon, "icon-name")
+					.transform_to(|_, muted: bool| {
+						let icon = if muted { icons::Icon::MicOff } else { icons::Icon::Mic };
+						Some(icon.name().to_string())
+					})
+					.sync_create()
+					.build();
+
+				bindings.borrow_mut().extend([label_binding, icon_binding]);
+			}
+		);
+
+		changed_default_mic(&Wp::default().audio());
+
+		Wp::default()
+			.audio()
+			.connect_notify_local(Some("default-microphone"), move |audio, _| changed_default_mic(audio));
+
+		Self { widget }
+	}
+
+	pub fn widget(&self) -> &gtk4::Button {
+		&self.widget
+	}
+}
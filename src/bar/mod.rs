@@ -8,8 +8,10 @@ use sysinfo::System;
 mod bluetooth;
 mod clock;
 mod mediaplayer;
+mod microphone;
 mod network;
 mod overview;
+mod privacy;
 mod taskbar;
 mod volume;
 
@@ -36,9 +38,11 @@ impl Bar {
 
 		let mediaplayer = mediaplayer::MediaPlayerWidget::new();
 
-		let volume = volume::Volume::new();
-		let network = network::Network::new();
-		let bluetooth = bluetooth::Bluetooth::new();
+		let volume = volume::Volume::new(args);
+		let microphone = microphone::Microphone::new(args);
+		let privacy = privacy::Privacy::new();
+		let network = network::Network::new(args);
+		let bluetooth = bluetooth::Bluetooth::new(args);
 		let clock = clock::Clock::new();
 
 		let end_box = gtk4::Box::builder()
@@ -48,6 +52,8 @@ impl Bar {
 			.spacing(8)
 			.build();
 
+		end_box.append(privacy.widget());
+		end_box.append(microphone.widget());
 		end_box.append(volume.widget());
 		end_box.append(network.widget());
 		end_box.append(bluetooth.widget());
@@ -1,20 +1,18 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-
-use astal_network::prelude::NetworkExt;
-use astal_network::{Primary, Wired};
+use astal_network::prelude::*;
+use astal_network::{Connectivity, Internet, Primary, Wired};
 use glib::clone;
-use gtk4::PropertyExpression;
 use gtk4::prelude::*;
+use libnm_api::prelude::*;
 
 use crate::icons;
+use crate::popups::network::{NetworkPopup, Throughput, active_vpns, wifi_icon};
 
 pub struct Network {
 	widget: gtk4::Button,
 }
 
 impl Network {
-	pub fn new() -> Self {
+	pub fn new(args: &crate::Args) -> Self {
 		let button_box = gtk4::Box::builder()
 			.name("network")
 			.orientation(gtk4::Orientation::Horizontal)
@@ -26,87 +24,137 @@ impl Network {
 			.child(&button_box)
 			.build();
 
+		let throughput = args.network_speed.then(Throughput::default);
+
+		let popup = NetworkPopup::new(throughput.as_ref());
+		popup.set_parent(&widget);
+
+		widget.connect_clicked(clone!(
+			#[weak]
+			popup,
+			move |_| {
+				popup.popup();
+			}
+		));
+
 		let nw = astal_network::Network::default();
 
 		if let Some(nw) = nw {
 			let icon = gtk4::Image::from_icon_name(icons::Icon::WifiOff.name());
 			let label = gtk4::Label::builder().build();
 
-			button_box.append(&icon);
-			button_box.append(&label);
-
-			nw.bind_property("primary", &icon, "icon-name")
-				.transform_to(|_, primary: Primary| {
-					let icon_name = match primary {
-						Primary::Wifi => icons::Icon::Wifi.name(),
-						Primary::Wired => icons::Icon::Network.name(),
-						_ => icons::Icon::WifiOff.name(),
-					};
-					Some(icon_name)
-				})
-				.sync_create()
+			let vpn_badge = gtk4::Image::builder()
+				.icon_name(icons::Icon::Lock.name())
+				.halign(gtk4::Align::End)
+				.valign(gtk4::Align::End)
+				.css_classes(["network-vpn-badge"])
+				.visible(false)
 				.build();
+			let icon_overlay = gtk4::Overlay::builder().child(&icon).build();
+			icon_overlay.add_overlay(&vpn_badge);
 
-			let current_binding = Rc::new(RefCell::new(None::<gtk4::ExpressionWatch>));
+			button_box.append(&icon_overlay);
+			button_box.append(&label);
 
-			let bind_network_name = clone!(
+			let update = clone!(
+				#[weak]
+				icon,
 				#[weak]
 				label,
-				#[strong]
-				current_binding,
+				#[weak]
+				vpn_badge,
+				#[weak]
+				widget,
 				move |nw: &astal_network::Network| {
-					let current = current_binding.borrow_mut().take();
-					if let Some(binding) = current {
-						binding.unwatch();
-					}
-
-					let binding = match (nw.primary(), nw.wired(), nw.wifi()) {
+					let (icon_name, name, internet) = match (nw.primary(), nw.wired(), nw.wifi()) {
 						(Primary::Wired, Some(wired), _) => {
-							let device_expr =
-								PropertyExpression::new(Wired::static_type(), gtk4::Expression::NONE, "device");
-							let device_name_expr = PropertyExpression::new(
-								libnm_api::Device::static_type(),
-								Some(device_expr),
-								"interface",
-							);
+							(icons::Icon::Network, wired_name(&wired), wired.internet())
+						}
+						(Primary::Wifi, _, Some(wifi)) => {
+							(wifi_icon(wifi.strength()), wifi.ssid().to_string(), wifi.internet())
+						}
+						_ => (icons::Icon::WifiOff, String::new(), Internet::Disconnected),
+					};
 
-							let binding = device_name_expr.bind(&label, "label", Some(&wired));
+					icon.set_icon_name(Some(icon_name.name()));
+					label.set_label(&name);
 
-							Some(binding)
+					widget.remove_css_class("connecting");
+					widget.remove_css_class("limited");
+					let tooltip = match (internet, nw.connectivity()) {
+						(Internet::Connecting, _) => {
+							widget.add_css_class("connecting");
+							Some(format!("Connecting to {}", name))
 						}
-						(Primary::Wifi, _, Some(wifi)) => {
-							let device_expr = PropertyExpression::new(
-								astal_network::Wifi::static_type(),
-								gtk4::Expression::NONE,
-								"device",
-							);
-							let device_name_expr = PropertyExpression::new(
-								libnm_api::Device::static_type(),
-								Some(device_expr),
-								"interface",
-							);
-							let binding = device_name_expr.bind(&label, "label", Some(&wifi));
-							Some(binding)
+						(Internet::Connected, Connectivity::Portal) => {
+							widget.add_css_class("limited");
+							Some("Sign-in required".to_string())
+						}
+						(Internet::Connected, Connectivity::Limited | Connectivity::None) => {
+							widget.add_css_class("limited");
+							Some("Limited connectivity".to_string())
 						}
-						_ => None,
+						(Internet::Connected, _) => Some(format!("Connected to {}", name)),
+						_ => Some("Disconnected".to_string()),
 					};
 
-					*current_binding.borrow_mut() = binding;
+					let vpns = active_vpns(&nw.client())
+						.iter()
+						.map(|connection| connection.id().to_string())
+						.collect::<Vec<_>>();
+					vpn_badge.set_visible(!vpns.is_empty());
+					let tooltip = match tooltip {
+						Some(tooltip) if !vpns.is_empty() => Some(format!("{}\nVPN: {}", tooltip, vpns.join(", "))),
+						tooltip => tooltip,
+					};
+					widget.set_tooltip_text(tooltip.as_deref());
 				}
 			);
 
-			bind_network_name(&nw);
+			update(&nw);
 
 			nw.connect_notify_local(
 				None,
 				clone!(
 					#[strong]
-					bind_network_name,
-					move |nw, _| {
-						bind_network_name(nw);
-					}
+					update,
+					move |nw, _| update(nw)
 				),
 			);
+
+			// Strength, SSID and connection state live on the devices, not on `Network` itself
+			let update_from_device = clone!(
+				#[weak]
+				nw,
+				move || update(&nw)
+			);
+			if let Some(wifi) = nw.wifi() {
+				let update_from_device = update_from_device.clone();
+				wifi.connect_notify_local(None, move |_, _| update_from_device());
+			}
+			if let Some(wired) = nw.wired() {
+				let update_from_device = update_from_device.clone();
+				wired.connect_notify_local(None, move |_, _| update_from_device());
+			}
+			// VPNs don't change the primary device, watch the client for them
+			nw.client()
+				.connect_active_connections_notify(move |_| update_from_device());
+		}
+
+		if let Some(throughput) = throughput {
+			let rates = gtk4::Label::builder().css_classes(["network-rates"]).build();
+			throughput.connect_transmitted_notify(clone!(
+				#[weak]
+				rates,
+				move |throughput| {
+					rates.set_label(&format!(
+						"↓{} ↑{}",
+						throughput.format_rate(throughput.received()),
+						throughput.format_rate(throughput.transmitted())
+					));
+				}
+			));
+			button_box.append(&rates);
 		}
 
 		Self { widget }
@@ -116,3 +164,13 @@ impl Network {
 		&self.widget
 	}
 }
+
+/// Name of the active wired connection profile, falling back to the interface.
+fn wired_name(wired: &Wired) -> String {
+	let device = wired.device();
+	// `active_connection()` assumes there is one, the property is `NULL` while disconnected
+	device
+		.property::<Option<libnm_api::ActiveConnection>>("active-connection")
+		.map(|connection| connection.id().to_string())
+		.unwrap_or_else(|| device.iface().to_string())
+}
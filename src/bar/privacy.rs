@@ -0,0 +1,259 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::time::Duration;
+
+use astal_wp::prelude::NodeExt;
+use astal_wp::{Endpoint, Node, NodeState, Stream, Wp};
+use glib::clone;
+use gtk4::prelude::*;
+
+use super::mediaplayer::capture::CAPTURE_NODE_NAME;
+use crate::icons::Icon;
+
+/// How long the indicator flashes after a new capture started
+const FLASH_DURATION: Duration = Duration::from_secs(2);
+
+/// Shows which kinds of capture (microphone, camera, screen) are running and by which apps.
+pub struct Privacy {
+	widget: gtk4::Box,
+}
+
+#[derive(Clone)]
+struct Indicators {
+	widget: gtk4::Box,
+	mic:    gtk4::Image,
+	camera: gtk4::Image,
+	screen: gtk4::Image,
+	/// Node ids of the capture streams seen in the last update, to notice new ones
+	known:  Rc<RefCell<HashSet<u32>>>,
+	flash:  Rc<RefCell<Option<glib::SourceId>>>,
+}
+
+impl Privacy {
+	pub fn new() -> Self {
+		let widget = gtk4::Box::builder()
+			.orientation(gtk4::Orientation::Horizontal)
+			.spacing(4)
+			.css_classes(["privacy"])
+			.visible(false)
+			.build();
+
+		let indicator = |icon: Icon| {
+			let image = gtk4::Image::from_icon_name(icon.name());
+			image.set_pixel_size(20);
+			widget.append(&image);
+			image
+		};
+
+		let indicators = Indicators {
+			mic:    indicator(Icon::Mic),
+			camera: indicator(Icon::Video),
+			screen: indicator(Icon::ScreenShare),
+			widget: widget.clone(),
+			known:  Rc::new(RefCell::new(HashSet::new())),
+			flash:  Rc::new(RefCell::new(None)),
+		};
+
+		let wp = Wp::default();
+		let audio = wp.audio();
+		let video = wp.video();
+
+		// Recorders are the capturing apps, video sources are cameras and screencasts
+		let nodes = audio
+			.recorders()
+			.into_iter()
+			.map(|stream| stream.upcast::<Node>())
+			.chain(video.recorders().into_iter().map(|stream| stream.upcast()))
+			.chain(video.sources().into_iter().map(|source| source.upcast()));
+		for node in nodes {
+			indicators.watch(&node);
+		}
+
+		audio.connect_recorder_added(clone!(
+			#[strong]
+			indicators,
+			move |_, stream| indicators.watch(stream.upcast_ref())
+		));
+		audio.connect_recorder_removed(clone!(
+			#[strong]
+			indicators,
+			move |_, _| indicators.update()
+		));
+		video.connect_recorder_added(clone!(
+			#[strong]
+			indicators,
+			move |_, stream| indicators.watch(stream.upcast_ref())
+		));
+		video.connect_recorder_removed(clone!(
+			#[strong]
+			indicators,
+			move |_, _| indicators.update()
+		));
+		video.connect_source_added(clone!(
+			#[strong]
+			indicators,
+			move |_, source| indicators.watch(source.upcast_ref())
+		));
+		video.connect_source_removed(clone!(
+			#[strong]
+			indicators,
+			move |_, _| indicators.update()
+		));
+
+		indicators.update();
+
+		Self { widget }
+	}
+
+	pub fn widget(&self) -> &gtk4::Box {
+		&self.widget
+	}
+}
+
+impl Indicators {
+	fn watch(&self, node: &Node) {
+		node.connect_state_notify(clone!(
+			#[strong(rename_to = indicators)]
+			self,
+			move |_| indicators.update()
+		));
+		self.update();
+	}
+
+	fn update(&self) {
+		let wp = Wp::default();
+		let audio = wp.audio();
+		let video = wp.video();
+
+		let mic_apps = audio
+			.recorders()
+			.into_iter()
+			.filter(|s| is_running(s) && !captures_sink(s))
+			.collect::<Vec<_>>();
+		let video_apps = video
+			.recorders()
+			.into_iter()
+			.filter(|s| is_running(s))
+			.collect::<Vec<_>>();
+
+		let (screens, cameras): (Vec<_>, Vec<_>) = video
+			.sources()
+			.into_iter()
+			.filter(|source| is_running(source))
+			.partition(is_screencast);
+		let recording = |sources: &[Endpoint]| {
+			video_apps
+				.iter()
+				.filter(|stream| sources.iter().any(|source| records_from(stream, source)))
+				.cloned()
+				.collect::<Vec<_>>()
+		};
+
+		let mut tooltip = Vec::new();
+		if !mic_apps.is_empty() {
+			tooltip.push(format!("Microphone: {}", app_names(&mic_apps)));
+		}
+		if !cameras.is_empty() {
+			tooltip.push(format!("Camera: {}", app_names(&recording(&cameras))));
+		}
+		if !screens.is_empty() {
+			tooltip.push(format!("Screen sharing: {}", app_names(&recording(&screens))));
+		}
+
+		self.mic.set_visible(!mic_apps.is_empty());
+		self.camera.set_visible(!cameras.is_empty());
+		self.screen.set_visible(!screens.is_empty());
+		self.widget.set_visible(!tooltip.is_empty());
+		self.widget.set_tooltip_text(Some(&tooltip.join("\n")));
+
+		let active = mic_apps
+			.iter()
+			.chain(&video_apps)
+			.map(|s| s.id())
+			.collect::<HashSet<_>>();
+		let started = active.difference(&self.known.borrow()).next().is_some();
+		self.known.replace(active);
+		if started {
+			self.flash();
+		}
+	}
+
+	fn flash(&self) {
+		self.widget.add_css_class("flash");
+
+		let widget = self.widget.clone();
+		let flash = self.flash.clone();
+		let source = glib::timeout_add_local_once(FLASH_DURATION, move || {
+			flash.take();
+			widget.remove_css_class("flash");
+		});
+		if let Some(previous) = self.flash.replace(Some(source)) {
+			previous.remove();
+		}
+	}
+}
+
+fn is_running(node: &impl IsA<Node>) -> bool {
+	node.state() == NodeState::Running
+}
+
+/// Whether a recorder reads from a sink's monitor rather than a microphone, like our own visualizer
+/// or a screen recorder grabbing desktop audio.
+fn captures_sink(stream: &Stream) -> bool {
+	if stream.name().as_deref() == Some(CAPTURE_NODE_NAME) {
+		return true;
+	}
+
+	let is_set = |key: &str| stream.pw_property(key).as_deref() == Some("true");
+	if is_set("stream.capture.sink") || (is_set("node.passive") && is_set("stream.monitor")) {
+		return true;
+	}
+
+	// Explicitly linked to a sink, by serial or by name
+	let Some(target) = stream.pw_property("target.object") else {
+		return false;
+	};
+	Wp::default()
+		.audio()
+		.speakers()
+		.iter()
+		.any(|sink| sink.serial().to_string() == target.as_str() || sink.name().as_deref() == Some(target.as_str()))
+}
+
+/// Cameras belong to a device, screencast nodes are created by the compositor or the portal without
+/// one.
+fn is_screencast(source: &Endpoint) -> bool {
+	let name = source.name().unwrap_or_default().to_lowercase();
+	source.device_id() == 0 || name.contains("xdg-desktop-portal") || name.contains("screencast")
+}
+
+/// Whether `stream` records `source`, going by the target PipeWire linked it to.
+fn records_from(stream: &Stream, source: &Endpoint) -> bool {
+	stream
+		.target_endpoint()
+		.is_some_and(|target| target.id() == source.id())
+		|| stream.target_serial() == source.serial()
+}
+
+fn app_names(streams: &[impl IsA<Node>]) -> String {
+	let mut names = streams
+		.iter()
+		.map(|stream| {
+			let description = stream.description().unwrap_or_default();
+			if description.is_empty() {
+				stream.name().unwrap_or_default().to_string()
+			} else {
+				description.to_string()
+			}
+		})
+		.collect::<Vec<_>>();
+	names.sort();
+	names.dedup();
+
+	if names.is_empty() {
+		"unknown".to_string()
+	} else {
+		names.join(", ")
+	}
+}
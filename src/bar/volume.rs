@@ -1,20 +1,19 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-
-use astal_wp::{Audio, Wp};
+use astal_wp::prelude::NodeExt;
+use astal_wp::{Endpoint, Wp};
 use glib::clone;
 use glib::object::ObjectExt;
+use gtk4::gio;
 use gtk4::prelude::*;
 
 use crate::icons;
-use crate::popups::volume::VolumePopup;
+use crate::popups::volume::{VolumePopup, default_endpoint, sink_icon, speaker_indicator, watch_default_speaker};
 
 pub struct Volume {
 	widget: gtk4::Button,
 }
 
 impl Volume {
-	pub fn new() -> Self {
+	pub fn new(args: &crate::Args) -> Self {
 		let button_box = gtk4::Box::builder()
 			.name("wireplumber")
 			.css_classes(["volume"])
@@ -22,10 +21,13 @@ impl Volume {
 			.spacing(4)
 			.build();
 
+		let sink_type_icon = gtk4::Image::from_icon_name(icons::Icon::Speaker.name());
+		sink_type_icon.set_pixel_size(24);
 		let volume_icon = gtk4::Image::from_icon_name(icons::Icon::VolumeOff.name());
 		volume_icon.set_pixel_size(24);
 		let label = gtk4::Label::builder().label("0%").build();
 
+		button_box.append(&sink_type_icon);
 		button_box.append(&volume_icon);
 		button_box.append(&label);
 
@@ -34,9 +36,6 @@ impl Volume {
 		let wp = Wp::default();
 		let audio = wp.audio();
 
-		let current_binding = Rc::new(RefCell::new(None::<glib::Binding>));
-		let current_icon_binding = Rc::new(RefCell::new(None::<glib::Binding>));
-
 		let popup = VolumePopup::new();
 		popup.set_parent(&widget);
 
@@ -48,51 +47,40 @@ impl Volume {
 			}
 		));
 
-		let changed_default_speaker = clone!(
-			#[strong]
-			current_binding,
+		add_device_menu(&widget, args.volume_menu_sources);
+
+		let update = clone!(
+			#[weak]
+			sink_type_icon,
 			#[weak]
 			label,
 			#[weak]
 			volume_icon,
-			move |audio: &Audio| {
-				if let Some(b) = current_binding.borrow_mut().take() {
-					b.unbind();
+			#[weak]
+			button_box,
+			move |speaker: Option<&Endpoint>| {
+				let indicator = speaker_indicator(speaker);
+				volume_icon.set_icon_name(Some(indicator.icon.name()));
+				sink_type_icon.set_visible(speaker.is_some());
+				if let Some(speaker) = speaker {
+					let route = speaker
+						.route()
+						.map(|route| route.name().to_string())
+						.unwrap_or_default();
+					sink_type_icon.set_icon_name(Some(sink_icon(&speaker.icon(), &route).name()));
 				}
-				if let Some(b) = current_icon_binding.borrow_mut().take() {
-					b.unbind();
+				label.set_label(&indicator.label);
+				if indicator.boosted {
+					button_box.add_css_class("boosted");
+				} else {
+					button_box.remove_css_class("boosted");
 				}
-
-				let speaker = audio.default_speaker();
-
-				let binding = speaker
-					.bind_property("volume", &label, "label")
-					.transform_to(|_, volume: f64| Some(format!("{:.0}%", volume * 100.0)))
-					.sync_create()
-					.build();
-				let icon_binding = speaker
-					.bind_property("volume", &volume_icon, "icon-name")
-					.transform_to(|_, volume: f64| {
-						let icon_name = match volume {
-							0.0 => icons::Icon::VolumeX,
-							0.0..=0.2 => icons::Icon::Volume,
-							0.2..=0.7 => icons::Icon::Volume1,
-							_ => icons::Icon::Volume2,
-						}
-						.name();
-						Some(icon_name.to_string())
-					})
-					.sync_create()
-					.build();
-
-				*current_binding.borrow_mut() = Some(binding);
-				*current_icon_binding.borrow_mut() = Some(icon_binding);
 			}
 		);
 
-		changed_default_speaker(&audio);
+		add_volume_controls(&widget, args, "default-speaker");
 
-		audio.connect_notify_local(Some("default-speaker"), move |audio, _| changed_default_speaker(audio));
+		watch_default_speaker(&audio, update);
 
 		Self { widget }
 	}
@@ -101,3 +89,121 @@ impl Volume {
 		&self.widget
 	}
 }
+
+/// Scrolling over `widget` steps the volume of the default endpoint named by `property`
+/// (`default-speaker` or `default-microphone`), middle-click toggles its mute.
+pub(super) fn add_volume_controls(widget: &gtk4::Button, args: &crate::Args, property: &'static str) {
+	let step = args.volume_step / 100.0;
+	let max_volume = args.max_volume / 100.0;
+
+	let scroll_controller = gtk4::EventControllerScroll::new(gtk4::EventControllerScrollFlags::VERTICAL);
+	scroll_controller.connect_scroll(move |_, _, dy| {
+		if dy == 0.0 {
+			return glib::Propagation::Proceed;
+		}
+
+		let Some(endpoint) = default_endpoint(&Wp::default().audio(), property) else {
+			return glib::Propagation::Proceed;
+		};
+		let volume = endpoint.volume();
+		// Don't pull down a volume that was boosted past the limit elsewhere when scrolling up
+		let limit = max_volume.max(volume);
+		endpoint.set_volume((volume - dy * step).clamp(0.0, limit));
+		glib::Propagation::Stop
+	});
+	widget.add_controller(scroll_controller);
+
+	let click_controller = gtk4::GestureClick::builder().button(gtk4::gdk::BUTTON_MIDDLE).build();
+	click_controller.connect_pressed(move |gesture, _, _, _| {
+		if let Some(endpoint) = default_endpoint(&Wp::default().audio(), property) {
+			endpoint.set_mute(!endpoint.is_muted());
+			gesture.set_state(gtk4::EventSequenceState::Claimed);
+		}
+	});
+	widget.add_controller(click_controller);
+}
+
+/// Right-click menu to pick the default output (and optionally input) without opening the popup.
+fn add_device_menu(widget: &gtk4::Button, with_sources: bool) {
+	let menu_model = gio::Menu::new();
+	let menu = gtk4::PopoverMenu::from_model(Some(&menu_model));
+	menu.set_has_arrow(false);
+	menu.set_parent(widget);
+
+	// Stateful actions with the default's node id as state render their items as radio buttons
+	let set_sink = gio::SimpleAction::new_stateful("set-sink", Some(glib::VariantTy::UINT32), &0u32.to_variant());
+	let set_source = gio::SimpleAction::new_stateful("set-source", Some(glib::VariantTy::UINT32), &0u32.to_variant());
+	for action in [&set_sink, &set_source] {
+		action.connect_activate(|action, parameter| {
+			let Some(id) = parameter.and_then(|parameter| parameter.get::<u32>()) else {
+				return;
+			};
+
+			let audio = Wp::default().audio();
+			let endpoint = if action.name() == "set-sink" {
+				audio.speakers()
+			} else {
+				audio.microphones()
+			}
+			.into_iter()
+			.find(|endpoint| endpoint.id() == id);
+			if let Some(endpoint) = endpoint {
+				endpoint.set_is_default(true);
+				action.set_state(&id.to_variant());
+			}
+		});
+	}
+
+	let actions = gio::SimpleActionGroup::new();
+	actions.add_action(&set_sink);
+	actions.add_action(&set_source);
+	widget.insert_action_group("volume", Some(&actions));
+
+	let click_controller = gtk4::GestureClick::builder()
+		.button(gtk4::gdk::BUTTON_SECONDARY)
+		.build();
+	click_controller.connect_pressed(clone!(
+		#[weak]
+		menu,
+		move |gesture, _, _, _| {
+			let audio = Wp::default().audio();
+			menu_model.remove_all();
+
+			let section = |action: &gio::SimpleAction, endpoints: Vec<Endpoint>, default: Option<Endpoint>| {
+				let section = gio::Menu::new();
+				for endpoint in endpoints {
+					let label = endpoint.description().or_else(|| endpoint.name()).unwrap_or_default();
+					let item = gio::MenuItem::new(Some(&label), None);
+					item.set_action_and_target_value(
+						Some(&format!("volume.{}", action.name())),
+						Some(&endpoint.id().to_variant()),
+					);
+					section.append_item(&item);
+				}
+				if let Some(default) = default {
+					action.set_state(&default.id().to_variant());
+				}
+				section
+			};
+
+			menu_model.append_section(
+				Some("Output"),
+				&section(&set_sink, audio.speakers(), default_endpoint(&audio, "default-speaker")),
+			);
+			if with_sources {
+				menu_model.append_section(
+					Some("Input"),
+					&section(
+						&set_source,
+						audio.microphones(),
+						default_endpoint(&audio, "default-microphone"),
+					),
+				);
+			}
+
+			gesture.set_state(gtk4::EventSequenceState::Claimed);
+			menu.popup();
+		}
+	));
+	widget.add_controller(click_controller);
+}
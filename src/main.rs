@@ -6,6 +6,7 @@ use gtk4::prelude::*;
 
 mod bar;
 mod icons;
+mod osd;
 mod popups;
 
 #[derive(Clone, Parser)]
@@ -16,8 +17,47 @@ pub struct Args {
 
 	#[clap(long)]
 	launcher: bool,
+
+	/// Volume change per scroll step over the bar volume and microphone, in percent
+	#[clap(long, default_value_t = 5.0)]
+	volume_step: f64,
+
+	/// Highest volume reachable by scrolling, in percent. Values above 100 allow over-amplification
+	#[clap(long, default_value_t = 100.0)]
+	max_volume: f64,
+
+	/// Also list input devices in the right-click menu of the bar volume
+	#[clap(long)]
+	volume_menu_sources: bool,
+
+	/// Make the next output the default in the running instance, meant for compositor keybinds
+	#[clap(long)]
+	cycle_output: bool,
+
+	/// Outputs whose description contains this are skipped when cycling, may be given multiple times
+	#[clap(long, value_name = "PATTERN")]
+	cycle_output_exclude: Vec<String>,
+
+	/// Show download and upload rates next to the network module
+	#[clap(long)]
+	network_speed: bool,
+
+	/// Unit for the network rates
+	#[clap(long, value_enum, default_value_t)]
+	network_speed_unit: popups::network::RateUnit,
+
+	/// Seconds between network rate samples
+	#[clap(long, default_value_t = 1.0)]
+	network_speed_interval: f64,
+
+	/// Warn when a connected Bluetooth device's battery drops below this percentage
+	#[clap(long, default_value_t = 20)]
+	bluetooth_battery_warning: u8,
 }
 
+const SIGINT: i32 = 2;
+const SIGTERM: i32 = 15;
+
 fn main() {
 	let args = Args::parse();
 
@@ -27,17 +67,75 @@ fn main() {
 
 	gtk4::Window::set_interactive_debugging(args.inspect);
 
-	app.connect_startup(|_| {
+	let exclude = args.cycle_output_exclude.clone();
+	let network_speed = args.network_speed.then(|| {
+		(
+			Duration::from_secs_f64(args.network_speed_interval.max(0.1)),
+			args.network_speed_unit,
+		)
+	});
+	app.connect_startup(move |app| {
 		println!("=== STARTUP CALLED ===");
 		load_css();
 		icons::register_bundled_icons();
 		gtk4::gio::resources_register_include!("assets.gresource").expect("Failed to load assets");
+
+		let cycle_output = gtk4::gio::SimpleAction::new("cycle-output", None);
+		let exclude = exclude.clone();
+		cycle_output.connect_activate(move |_, _| popups::volume::cycle_output(&exclude));
+		app.add_action(&cycle_output);
+
+		// Once per process, `activate` runs again whenever niribar is launched a second time
+		let osd = osd::Osd::new();
+		app.add_window(&osd.window);
+		popups::bluetooth::register_agent(app);
+
+		// Sampled once here and shared by the bars on every monitor
+		if let Some((interval, unit)) = network_speed {
+			popups::network::Throughput::default().start(interval, unit);
+		}
+
+		// Quit cleanly so shutdown handlers get to restore things like Bluetooth discovery
+		for signal in [SIGINT, SIGTERM] {
+			let app = app.downgrade();
+			glib::unix_signal_add_local(signal, move || {
+				if let Some(app) = app.upgrade() {
+					app.quit();
+				}
+				glib::ControlFlow::Break
+			});
+		}
 	});
 	app.connect_activate(build_ui(args.clone()));
 
+	if args.cycle_output {
+		send_cycle_output(&app);
+		return;
+	}
+
 	app.run_with_args::<String>(&[]);
 }
 
+/// Forwards `--cycle-output` to the already running instance.
+fn send_cycle_output(app: &gtk4::Application) {
+	if let Err(e) = app.register(None::<&gtk4::gio::Cancellable>) {
+		eprintln!("Failed to register application: {}", e);
+		return;
+	}
+	if !app.is_remote() {
+		eprintln!("niribar is not running");
+		return;
+	}
+
+	app.activate_action("cycle-output", None);
+	// Remote actions are sent asynchronously, make sure it's out before we exit
+	if let Some(connection) = app.dbus_connection()
+		&& let Err(e) = connection.flush_sync(None::<&gtk4::gio::Cancellable>)
+	{
+		eprintln!("Failed to send cycle-output: {}", e);
+	}
+}
+
 fn load_css() {
 	let provider = gtk4::CssProvider::new();
 	provider.load_from_string(include_str!("./style.css"));
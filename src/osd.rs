@@ -0,0 +1,210 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use astal_wp::prelude::*;
+use astal_wp::{Audio, Endpoint, Wp};
+use astal4::prelude::*;
+use glib::{SignalHandlerId, clone};
+use gtk4::gdk;
+use gtk4::prelude::*;
+use niri_ipc::socket::Socket;
+use niri_ipc::{Event, Request, Response, Workspace};
+
+use crate::icons::Icon;
+use crate::popups::volume::{self, SpeakerIndicator};
+
+const HIDE_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Volume on-screen display, shown on the focused output whenever the default speaker or microphone
+/// changes volume or mute.
+#[derive(Clone)]
+pub struct Osd {
+	pub window: astal4::Window,
+	icon:       gtk4::Image,
+	progress:   gtk4::ProgressBar,
+	label:      gtk4::Label,
+	hide:       Rc<RefCell<Option<glib::SourceId>>>,
+	/// Connector name of niri's focused output, kept current from the event stream
+	focused:    Rc<RefCell<Option<String>>>,
+}
+
+impl Osd {
+	pub fn new() -> Self {
+		let icon = gtk4::Image::from_icon_name(Icon::Volume2.name());
+		icon.set_pixel_size(32);
+		let progress = gtk4::ProgressBar::builder()
+			.valign(gtk4::Align::Center)
+			.hexpand(true)
+			.build();
+		let label = gtk4::Label::builder().width_chars(4).build();
+
+		let content = gtk4::Box::builder()
+			.orientation(gtk4::Orientation::Horizontal)
+			.spacing(12)
+			.css_classes(["osd-content"])
+			.build();
+		content.append(&icon);
+		content.append(&progress);
+		content.append(&label);
+
+		let window = astal4::Window::builder()
+			.layer(astal4::Layer::Overlay)
+			.exclusivity(astal4::Exclusivity::Ignore)
+			.keymode(astal4::Keymode::None)
+			.child(&content)
+			.name("osd")
+			.css_classes(["osd"])
+			.build();
+
+		let osd = Self {
+			window,
+			icon,
+			progress,
+			label,
+			hide: Rc::new(RefCell::new(None)),
+			focused: Rc::new(RefCell::new(None)),
+		};
+		osd.track_focused_output();
+
+		let audio = Wp::default().audio();
+		osd.follow_default(&audio, "default-speaker", |speaker| {
+			volume::speaker_indicator(Some(speaker))
+		});
+		osd.follow_default(&audio, "default-microphone", |mic| SpeakerIndicator {
+			icon: if mic.is_muted() { Icon::MicOff } else { Icon::Mic },
+			..volume::speaker_indicator(Some(mic))
+		});
+
+		osd
+	}
+
+	/// Watches volume and mute of whatever endpoint `property` of [`Audio`] currently points to.
+	/// Switching the default itself does not show the OSD.
+	fn follow_default(&self, audio: &Audio, property: &'static str, indicator: fn(&Endpoint) -> SpeakerIndicator) {
+		let watched = Rc::new(RefCell::new(None::<(Endpoint, Vec<SignalHandlerId>)>));
+
+		let osd = self.clone();
+		let rebind = move |audio: &Audio| {
+			if let Some((endpoint, handlers)) = watched.borrow_mut().take() {
+				for handler in handlers {
+					endpoint.disconnect(handler);
+				}
+			}
+
+			let Some(endpoint) = audio.property::<Option<Endpoint>>(property) else {
+				return;
+			};
+			let handlers = ["volume", "mute"]
+				.into_iter()
+				.map(|changed| {
+					endpoint.connect_notify_local(
+						Some(changed),
+						clone!(
+							#[strong]
+							osd,
+							move |endpoint, _| osd.show(&indicator(endpoint), endpoint.volume())
+						),
+					)
+				})
+				.collect();
+			watched.replace(Some((endpoint, handlers)));
+		};
+
+		rebind(audio);
+		audio.connect_notify_local(Some(property), move |audio, _| rebind(audio));
+	}
+
+	fn show(&self, indicator: &SpeakerIndicator, volume: f64) {
+		// The popup's own controls already show the volume, and dragging a slider would flood us
+		if volume::is_local_change() {
+			return;
+		}
+
+		self.icon.set_icon_name(Some(indicator.icon.name()));
+		self.progress.set_fraction(volume.clamp(0.0, 1.0));
+		self.label.set_label(&indicator.label);
+
+		if !self.window.is_visible()
+			&& let Some(monitor) = self.focused_monitor()
+		{
+			self.window.set_gdkmonitor(Some(&monitor));
+		}
+		self.window.set_visible(true);
+
+		let window = self.window.clone();
+		let hide = self.hide.clone();
+		let source = glib::timeout_add_local_once(HIDE_TIMEOUT, move || {
+			hide.take();
+			window.set_visible(false);
+		});
+		if let Some(previous) = self.hide.replace(Some(source)) {
+			previous.remove();
+		}
+	}
+
+	/// Looks up the monitor of niri's focused output, `None` lets the compositor decide.
+	fn focused_monitor(&self) -> Option<gdk::Monitor> {
+		let focused = self.focused.borrow();
+		let output = focused.as_deref()?;
+
+		gdk::Display::default()?
+			.monitors()
+			.iter::<gdk::Monitor>()
+			.filter_map(Result::ok)
+			.find(|monitor| monitor.connector().as_deref() == Some(output))
+	}
+
+	/// Follows the focused output on a thread reading niri's event stream, so showing the OSD
+	/// never waits on IPC.
+	fn track_focused_output(&self) {
+		let (tx, rx) = async_channel::unbounded();
+		std::thread::spawn(move || focused_output_stream(tx));
+
+		let focused = self.focused.clone();
+		glib::spawn_future_local(async move {
+			while let Ok(output) = rx.recv().await {
+				focused.replace(output);
+			}
+		});
+	}
+}
+
+fn focused_output_stream(tx: async_channel::Sender<Option<String>>) {
+	let mut socket = match Socket::connect() {
+		Ok(socket) => socket,
+		Err(e) => {
+			eprintln!(
+				"Failed to connect to niri, the OSD follows the compositor's choice: {}",
+				e
+			);
+			return;
+		}
+	};
+	if !matches!(socket.send(Request::EventStream), Ok(Ok(Response::Handled))) {
+		eprintln!("Failed to request the niri event stream for the OSD");
+		return;
+	}
+
+	let mut read_event = socket.read_events();
+	let mut workspaces = Vec::<Workspace>::new();
+	while let Ok(event) = read_event() {
+		match event {
+			Event::WorkspacesChanged { workspaces: changed } => workspaces = changed,
+			Event::WorkspaceActivated { id, focused: true } => {
+				for workspace in &mut workspaces {
+					workspace.is_focused = workspace.id == id;
+				}
+			}
+			_ => continue,
+		}
+
+		let output = workspaces
+			.iter()
+			.find(|workspace| workspace.is_focused)
+			.and_then(|workspace| workspace.output.clone());
+		if tx.send_blocking(output).is_err() {
+			return;
+		}
+	}
+}
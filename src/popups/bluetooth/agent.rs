@@ -0,0 +1,277 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use astal_bluetooth::Bluetooth;
+use astal_bluetooth::prelude::{BluetoothExt, DeviceExt};
+use glib::clone;
+use glib::variant::ObjectPath;
+use gtk4::gio;
+use gtk4::prelude::*;
+
+const AGENT_PATH: &str = "/org/niribar/BluetoothAgent";
+const AGENT_XML: &str = r#"
+<node>
+  <interface name="org.bluez.Agent1">
+    <method name="Release"/>
+    <method name="RequestPinCode">
+      <arg name="device" type="o" direction="in"/>
+      <arg name="pincode" type="s" direction="out"/>
+    </method>
+    <method name="DisplayPinCode">
+      <arg name="device" type="o" direction="in"/>
+      <arg name="pincode" type="s" direction="in"/>
+    </method>
+    <method name="RequestPasskey">
+      <arg name="device" type="o" direction="in"/>
+      <arg name="passkey" type="u" direction="out"/>
+    </method>
+    <method name="DisplayPasskey">
+      <arg name="device" type="o" direction="in"/>
+      <arg name="passkey" type="u" direction="in"/>
+      <arg name="entered" type="q" direction="in"/>
+    </method>
+    <method name="RequestConfirmation">
+      <arg name="device" type="o" direction="in"/>
+      <arg name="passkey" type="u" direction="in"/>
+    </method>
+    <method name="RequestAuthorization">
+      <arg name="device" type="o" direction="in"/>
+    </method>
+    <method name="AuthorizeService">
+      <arg name="device" type="o" direction="in"/>
+      <arg name="uuid" type="s" direction="in"/>
+    </method>
+    <method name="Cancel"/>
+  </interface>
+</node>
+"#;
+
+/// What the user is asked for.
+enum Prompt {
+	/// Accept or reject
+	Confirm,
+	/// Type in a code shown on the other device
+	Entry,
+	/// Only show a code to type in on the other device
+	Display,
+}
+
+/// Registers niribar as the default BlueZ pairing agent, so pairing requests show a dialog
+/// instead of failing.
+pub fn register_agent(app: &gtk4::Application) {
+	let app = app.clone();
+	glib::spawn_future_local(async move {
+		if let Err(e) = register(&app).await {
+			eprintln!("Failed to register Bluetooth agent: {}", e);
+		}
+	});
+}
+
+async fn register(app: &gtk4::Application) -> Result<(), Box<dyn std::error::Error>> {
+	let connection = gio::bus_get_future(gio::BusType::System).await?;
+
+	let node = gio::DBusNodeInfo::for_xml(AGENT_XML)?;
+	let interface = node
+		.lookup_interface("org.bluez.Agent1")
+		.ok_or("Agent1 missing from introspection data")?;
+
+	// Dialog of the request in progress, BlueZ only runs one at a time
+	let dialog = Rc::new(RefCell::new(None::<gtk4::Window>));
+
+	connection
+		.register_object(AGENT_PATH, &interface)
+		.method_call(clone!(
+			#[weak]
+			app,
+			move |_, _, _, _, method, parameters, invocation| {
+				handle_method(&app, &dialog, method, &parameters, invocation);
+			}
+		))
+		.build()?;
+
+	let path = ObjectPath::try_from(AGENT_PATH)?;
+	for (method, parameters) in [
+		("RegisterAgent", (path.clone(), "KeyboardDisplay").to_variant()),
+		("RequestDefaultAgent", (path.clone(),).to_variant()),
+	] {
+		connection
+			.call_future(
+				Some("org.bluez"),
+				"/org/bluez",
+				"org.bluez.AgentManager1",
+				method,
+				Some(&parameters),
+				None,
+				gio::DBusCallFlags::NONE,
+				-1,
+			)
+			.await?;
+	}
+
+	Ok(())
+}
+
+fn handle_method(
+	app: &gtk4::Application, dialog: &Rc<RefCell<Option<gtk4::Window>>>, method: &str, parameters: &glib::Variant,
+	invocation: gio::DBusMethodInvocation,
+) {
+	if let Some(window) = dialog.take() {
+		window.close();
+	}
+
+	let device = parameters
+		.try_child_value(0)
+		.and_then(|path| path.str().map(device_name))
+		.unwrap_or_default();
+
+	let (body, prompt) = match method {
+		"RequestPinCode" => (format!("Enter the PIN for {}", device), Prompt::Entry),
+		"RequestPasskey" => (format!("Enter the passkey for {}", device), Prompt::Entry),
+		"DisplayPinCode" => {
+			let pin = parameters.child_get::<String>(1);
+			(format!("Enter {} on {}", pin, device), Prompt::Display)
+		}
+		"DisplayPasskey" => {
+			let passkey = parameters.child_get::<u32>(1);
+			(format!("Enter {:06} on {}", passkey, device), Prompt::Display)
+		}
+		"RequestConfirmation" => {
+			let passkey = parameters.child_get::<u32>(1);
+			(
+				format!("Does {} show the passkey {:06}?", device, passkey),
+				Prompt::Confirm,
+			)
+		}
+		"RequestAuthorization" => (format!("Allow {} to pair?", device), Prompt::Confirm),
+		// Only asked for devices that aren't trusted, trusted ones are let through by BlueZ itself
+		"AuthorizeService" => {
+			let uuid = parameters.child_get::<String>(1);
+			(
+				format!("Allow {} to use the service {}?", device, uuid),
+				Prompt::Confirm,
+			)
+		}
+		// "Cancel" and "Release" only need the dialog gone
+		_ => {
+			invocation.return_value(None);
+			return;
+		}
+	};
+
+	let (tx, rx) = async_channel::bounded::<Option<String>>(1);
+	let accept = if method == "AuthorizeService" { "Allow" } else { "Pair" };
+	let window = prompt_window(app, &body, &prompt, accept, tx);
+	dialog.replace(Some(window.clone()));
+	window.present();
+
+	if let Prompt::Display = prompt {
+		// Nothing to answer, BlueZ cancels or releases us once the code has been typed
+		invocation.return_value(None);
+		return;
+	}
+
+	let method = method.to_string();
+	glib::spawn_future_local(async move {
+		// The channel closes without a value if BlueZ cancelled and the window got closed
+		let answer = rx.recv().await.ok().flatten();
+
+		match (method.as_str(), answer) {
+			(_, None) => invocation.return_dbus_error("org.bluez.Error.Rejected", "Rejected by user"),
+			("RequestPinCode", Some(pin)) => invocation.return_value(Some(&(pin,).to_variant())),
+			("RequestPasskey", Some(passkey)) => match passkey.trim().parse::<u32>() {
+				Ok(passkey) => invocation.return_value(Some(&(passkey,).to_variant())),
+				Err(_) => invocation.return_dbus_error("org.bluez.Error.Rejected", "Invalid passkey"),
+			},
+			_ => invocation.return_value(None),
+		}
+	});
+}
+
+/// Sends the entered text (or an empty string when confirming) through `tx`, or nothing when
+/// cancelled.
+fn prompt_window(
+	app: &gtk4::Application, body: &str, prompt: &Prompt, accept: &str, tx: async_channel::Sender<Option<String>>,
+) -> gtk4::Window {
+	let content = gtk4::Box::builder()
+		.orientation(gtk4::Orientation::Vertical)
+		.spacing(12)
+		.css_classes(["bluetooth-agent"])
+		.build();
+	content.append(&gtk4::Label::builder().label(body).wrap(true).build());
+
+	let entry = gtk4::Entry::builder().activates_default(true).build();
+	if let Prompt::Entry = prompt {
+		content.append(&entry);
+	}
+
+	let buttons = gtk4::Box::builder()
+		.orientation(gtk4::Orientation::Horizontal)
+		.spacing(6)
+		.halign(gtk4::Align::End)
+		.build();
+	content.append(&buttons);
+
+	let window = gtk4::Window::builder()
+		.application(app)
+		.title("Bluetooth pairing")
+		.resizable(false)
+		.child(&content)
+		.build();
+
+	let cancel = gtk4::Button::with_label(match prompt {
+		Prompt::Display => "Close",
+		_ => "Cancel",
+	});
+	cancel.connect_clicked(clone!(
+		#[weak]
+		window,
+		move |_| window.close()
+	));
+	buttons.append(&cancel);
+
+	if !matches!(prompt, Prompt::Display) {
+		let accept = gtk4::Button::builder()
+			.label(accept)
+			.css_classes(["suggested-action"])
+			.build();
+		accept.connect_clicked(clone!(
+			#[weak]
+			window,
+			#[weak]
+			entry,
+			#[strong]
+			tx,
+			move |_| {
+				let _ = tx.try_send(Some(entry.text().to_string()));
+				window.close();
+			}
+		));
+		buttons.append(&accept);
+		window.set_default_widget(Some(&accept));
+	}
+
+	// Closing any other way counts as a rejection
+	window.connect_close_request(move |_| {
+		let _ = tx.try_send(None);
+		glib::Propagation::Proceed
+	});
+
+	window
+}
+
+/// Alias of the device at a BlueZ object path such as `/org/bluez/hci0/dev_AA_BB_CC_DD_EE_FF`.
+fn device_name(path: &str) -> String {
+	let address = path
+		.rsplit('/')
+		.next()
+		.and_then(|name| name.strip_prefix("dev_"))
+		.unwrap_or(path)
+		.replace('_', ":");
+
+	Bluetooth::default()
+		.devices()
+		.into_iter()
+		.find(|device| device.address().eq_ignore_ascii_case(&address))
+		.map(|device| device.alias().to_string())
+		.unwrap_or(address)
+}
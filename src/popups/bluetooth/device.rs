@@ -2,11 +2,12 @@ use std::cell::RefCell;
 
 use astal_bluetooth::Device;
 use astal_bluetooth::prelude::*;
-use glib::{Properties, clone};
+use glib::{GString, Properties, clone};
 use gtk4::CompositeTemplate;
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
 
+use super::{battery_icon, battery_level};
 use crate::icons::Icon;
 
 glib::wrapper! {
@@ -29,10 +30,15 @@ mod imp {
 	use glib::ffi::GError;
 	use glib::gobject_ffi::GObject;
 	use glib::translate::ToGlibPtr;
+	use glib::variant::ObjectPath;
+	use gtk4::gio;
 	use gtk4::gio::ffi::{GAsyncReadyCallback, GAsyncResult};
 
 	use super::*;
 
+	/// Pairing waits on the user confirming on both ends, D-Bus' default of 25s is too short
+	const PAIR_TIMEOUT_MS: i32 = 120_000;
+
 	#[derive(Default, Properties, CompositeTemplate)]
 	#[template(file = "./src/popups/bluetooth/device.blp")]
 	#[properties(wrapper_type = super::BluetoothDevice)]
@@ -108,6 +114,8 @@ mod imp {
 			device.connect_connecting_notify(move |device| {
 				update_style(device);
 			});
+
+			self.add_menu(device);
 		}
 	}
 
@@ -118,7 +126,30 @@ mod imp {
 			if let Some(device) = device.as_ref() {
 				let device_is_connected = device.is_connected() || device.is_connecting();
 
-				if !device_is_connected {
+				if !device.is_paired() {
+					// Connecting right away fails for devices that need confirmation, pairing goes
+					// through our agent
+					glib::spawn_future_local(clone!(
+						#[weak]
+						device,
+						async move {
+							match device.pair_device().await {
+								Ok(_) => {
+									// Deliberately trusted right away: the user just confirmed pairing in
+									// our agent, and untrusted devices would otherwise need every service
+									// authorized again on each reconnect. Untrust it from the context menu.
+									device.set_trusted(true);
+									if let Err(e) = device.connect_device().await {
+										eprintln!("Failed to connect to device {:?}: {}", device, e);
+									}
+								}
+								Err(e) => {
+									eprintln!("Failed to pair with device {:?}: {}", device, e);
+								}
+							}
+						}
+					));
+				} else if !device_is_connected {
 					glib::spawn_future_local(clone!(
 						#[weak]
 						device,
@@ -172,11 +203,95 @@ mod imp {
 				_ => Icon::Bluetooth.name(),
 			}
 		}
+
+		#[template_callback]
+		fn has_battery(&self, percentage: f64) -> bool {
+			battery_level(percentage).is_some()
+		}
+
+		#[template_callback]
+		fn to_battery_icon(&self, percentage: f64) -> &'static str {
+			battery_icon(battery_level(percentage).unwrap_or_default(), false).name()
+		}
+
+		#[template_callback]
+		fn format_battery(&self, percentage: f64) -> GString {
+			format!("{}%", battery_level(percentage).unwrap_or_default()).into()
+		}
+	}
+
+	impl BluetoothDevice {
+		/// Right-click menu with trust and forget actions.
+		fn add_menu(&self, device: &Device) {
+			let obj = self.obj();
+
+			let menu_model = gio::Menu::new();
+			menu_model.append(Some("Trusted"), Some("device.trusted"));
+			menu_model.append(Some("Forget"), Some("device.forget"));
+
+			let menu = gtk4::PopoverMenu::from_model(Some(&menu_model));
+			menu.set_has_arrow(false);
+			menu.set_parent(&*obj);
+
+			let trusted = gio::SimpleAction::new_stateful("trusted", None, &device.is_trusted().to_variant());
+			trusted.connect_activate(clone!(
+				#[weak]
+				device,
+				move |_, _| device.set_trusted(!device.is_trusted())
+			));
+			device.connect_trusted_notify(clone!(
+				#[weak]
+				trusted,
+				move |device| trusted.set_state(&device.is_trusted().to_variant())
+			));
+
+			let forget = gio::SimpleAction::new("forget", None);
+			forget.connect_activate(clone!(
+				#[weak]
+				device,
+				move |_, _| {
+					glib::spawn_future_local(clone!(
+						#[weak]
+						device,
+						async move {
+							if let Err(e) = device.remove_device().await {
+								eprintln!("Failed to forget device {:?}: {}", device, e);
+							}
+						}
+					));
+				}
+			));
+
+			// Only paired devices have anything to trust or forget
+			for action in [&trusted, &forget] {
+				device.bind_property("paired", action, "enabled").sync_create().build();
+			}
+
+			let actions = gio::SimpleActionGroup::new();
+			actions.add_action(&trusted);
+			actions.add_action(&forget);
+			obj.insert_action_group("device", Some(&actions));
+
+			let click_controller = gtk4::GestureClick::builder()
+				.button(gtk4::gdk::BUTTON_SECONDARY)
+				.build();
+			click_controller.connect_pressed(clone!(
+				#[weak]
+				menu,
+				move |gesture, _, _, _| {
+					gesture.set_state(gtk4::EventSequenceState::Claimed);
+					menu.popup();
+				}
+			));
+			obj.add_controller(click_controller);
+		}
 	}
 
 	trait BtDeviceExt {
 		async fn connect_device(&self) -> Result<(), Box<dyn std::error::Error>>;
 		async fn disconnect_device(&self) -> Result<(), Box<dyn std::error::Error>>;
+		async fn pair_device(&self) -> Result<(), Box<dyn std::error::Error>>;
+		async fn remove_device(&self) -> Result<(), Box<dyn std::error::Error>>;
 	}
 
 	struct ContextData {
@@ -255,6 +370,77 @@ mod imp {
 				Err(e) => Err(Box::new(e)),
 			}
 		}
+
+		async fn pair_device(&self) -> Result<(), Box<dyn std::error::Error>> {
+			// astal has no getter for the adapter's object path, only the property
+			let adapter = self.property::<String>("adapter");
+			let (connection, path) = device_path(&self.address(), &adapter).await?;
+			connection
+				.call_future(
+					Some("org.bluez"),
+					&path,
+					"org.bluez.Device1",
+					"Pair",
+					None,
+					None,
+					gio::DBusCallFlags::NONE,
+					PAIR_TIMEOUT_MS,
+				)
+				.await?;
+			Ok(())
+		}
+
+		async fn remove_device(&self) -> Result<(), Box<dyn std::error::Error>> {
+			let adapter = self.property::<String>("adapter");
+			let (connection, path) = device_path(&self.address(), &adapter).await?;
+			connection
+				.call_future(
+					Some("org.bluez"),
+					&adapter,
+					"org.bluez.Adapter1",
+					"RemoveDevice",
+					Some(&(ObjectPath::try_from(path.as_str())?,).to_variant()),
+					None,
+					gio::DBusCallFlags::NONE,
+					-1,
+				)
+				.await?;
+			Ok(())
+		}
+	}
+
+	/// Looks up the BlueZ object path of the device with `address` on the adapter at `adapter`,
+	/// which astal doesn't expose. The same device shows up once per adapter that has seen it.
+	async fn device_path(
+		address: &str, adapter: &str,
+	) -> Result<(gio::DBusConnection, String), Box<dyn std::error::Error>> {
+		let connection = gio::bus_get_future(gio::BusType::System).await?;
+		let reply = connection
+			.call_future(
+				Some("org.bluez"),
+				"/",
+				"org.freedesktop.DBus.ObjectManager",
+				"GetManagedObjects",
+				None,
+				Some(glib::VariantTy::new("(a{oa{sa{sv}}})")?),
+				gio::DBusCallFlags::NONE,
+				-1,
+			)
+			.await?;
+
+		let path = reply.child_value(0).iter().find_map(|object| {
+			let device = object.child_value(1).lookup_value("org.bluez.Device1", None)?;
+			let device_address = device.lookup_value("Address", None)?.get::<String>()?;
+			let device_adapter = device.lookup_value("Adapter", None)?;
+			(device_address.eq_ignore_ascii_case(address) && device_adapter.str() == Some(adapter))
+				.then(|| object.child_value(0).str().map(str::to_string))
+				.flatten()
+		});
+
+		match path {
+			Some(path) => Ok((connection, path)),
+			None => Err(format!("No BlueZ device with address {} on {}", address, adapter).into()),
+		}
 	}
 
 	unsafe extern "C" fn callback(source_object: *mut GObject, result: *mut GAsyncResult, user_data: *mut c_void) {
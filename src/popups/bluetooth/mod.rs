@@ -1,13 +1,18 @@
+mod agent;
 mod device;
 
 use std::cell::RefCell;
+use std::time::{Duration, Instant};
 
+pub use agent::register_agent;
 use astal_bluetooth::Bluetooth;
-use glib::{Properties, clone};
+use glib::{ControlFlow, Properties, clone};
 use gtk4::gio::ListStore;
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
-use gtk4::{CompositeTemplate, SignalListItemFactory};
+use gtk4::{CompositeTemplate, SignalListItemFactory, gio};
+
+use crate::icons::Icon;
 
 glib::wrapper! {
 	pub struct BluetoothPopup(ObjectSubclass<imp::BluetoothPopup>)
@@ -21,27 +26,76 @@ impl BluetoothPopup {
 	}
 }
 
+/// Battery level in percent from a device's `battery-percentage`, if it reports one through
+/// `org.bluez.Battery1`.
+pub fn battery_level(percentage: f64) -> Option<u8> {
+	(percentage > 0.0).then(|| (percentage * 100.0).round().clamp(0.0, 100.0) as u8)
+}
+
+pub fn battery_icon(level: u8, low: bool) -> Icon {
+	match level {
+		_ if low => Icon::BatteryWarning,
+		67.. => Icon::BatteryFull,
+		34..67 => Icon::BatteryMedium,
+		_ => Icon::BatteryLow,
+	}
+}
+
 mod imp {
 
 	use astal_bluetooth::prelude::{AdapterExt, BluetoothExt, DeviceExt};
-	use gtk4::SortListModel;
+	use astal_bluetooth::{Adapter, Device};
+	use gtk4::{CustomFilter, CustomSorter, FilterListModel, SortListModel};
 
 	use super::*;
 
-	#[derive(Default, Clone, Copy)]
-	struct AdapterState {
-		discoverable: bool,
-		pairable:     bool,
-		discovering:  bool,
+	/// How long a scan runs before discovery is stopped again
+	const SCAN_TIMEOUT: Duration = Duration::from_secs(30);
+	/// BlueZ turns discoverability off by itself after this, even if we never get to
+	const DISCOVERABLE_TIMEOUT_S: u32 = 180;
+
+	/// Adapter settings we changed, undone when we're done with them or on shutdown
+	#[derive(Default)]
+	struct AdapterChanges {
+		/// Discovery we started, as opposed to joining someone else's
+		discovery:  Option<Adapter>,
+		/// `(discoverable, pairable)` before the user asked to be visible
+		visibility: Option<(Adapter, bool, bool)>,
 	}
 
 	#[derive(Default, Properties, CompositeTemplate)]
 	#[template(file = "./src/popups/bluetooth/bluetooth.blp")]
 	#[properties(wrapper_type = super::BluetoothPopup)]
 	pub struct BluetoothPopup {
+		/// Also list unpaired devices that don't advertise a name
+		#[property(get, set)]
+		show_unnamed: RefCell<bool>,
+
+		/// Adapter the header controls and discovery runs on
+		#[property(get, set)]
+		adapter:            RefCell<Option<Adapter>>,
+		#[property(get, set)]
+		has_adapter_choice: RefCell<bool>,
+		#[property(get, set)]
+		scanning:           RefCell<bool>,
+		/// Fraction of `SCAN_TIMEOUT` that has passed
+		#[property(get, set)]
+		scan_progress:      RefCell<f64>,
+		/// Visible to other devices, follows the adapter
+		#[property(get, set)]
+		discoverable:       RefCell<bool>,
+
+		#[template_child]
+		list_view:        TemplateChild<gtk4::ListView>,
 		#[template_child]
-		list_view:      TemplateChild<gtk4::ListView>,
-		previous_state: RefCell<Option<AdapterState>>,
+		adapter_dropdown: TemplateChild<gtk4::DropDown>,
+		#[template_child]
+		power_switch:     TemplateChild<gtk4::Switch>,
+
+		adapters:         RefCell<Vec<Adapter>>,
+		adapter_bindings: RefCell<Vec<glib::Binding>>,
+		changes:          RefCell<AdapterChanges>,
+		scan_timer:       RefCell<Option<glib::SourceId>>,
 	}
 
 	#[glib::object_subclass]
@@ -53,7 +107,7 @@ mod imp {
 
 		fn class_init(klass: &mut Self::Class) {
 			klass.bind_template();
-			// klass.bind_template_callbacks();
+			klass.bind_template_callbacks();
 		}
 
 		fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -69,7 +123,7 @@ mod imp {
 
 			let bt = Bluetooth::default();
 
-			let model = ListStore::builder().build();
+			let model = ListStore::new::<device::BluetoothDevice>();
 			let factory = SignalListItemFactory::new();
 
 			factory.connect_setup(move |_, _item| {});
@@ -88,27 +142,75 @@ mod imp {
 				list_item.set_child(None::<&gtk4::Widget>);
 			});
 
-			let add_devices = clone!(
+			// Sections: devices we know first, then whatever discovery turns up
+			let section_sorter = CustomSorter::new(|a, b| {
+				let a = device_of(a);
+				let b = device_of(b);
+				is_known(&b).cmp(&is_known(&a)).into()
+			});
+
+			// Within a section connected devices first, then paired, then by name
+			let sorter = CustomSorter::new(|a, b| {
+				let a = device_of(a);
+				let b = device_of(b);
+
+				is_known(&b)
+					.cmp(&is_known(&a))
+					.then_with(|| b.is_connected().cmp(&a.is_connected()))
+					.then_with(|| b.is_paired().cmp(&a.is_paired()))
+					.then_with(|| display_name(&a).to_lowercase().cmp(&display_name(&b).to_lowercase()))
+					.then_with(|| a.address().cmp(&b.address()))
+					.into()
+			});
+
+			// Unpaired devices without a name are mostly random-MAC beacons
+			let filter = CustomFilter::new(clone!(
 				#[weak]
-				model,
-				move |bt: &Bluetooth| {
-					model.remove_all();
-					for device in bt.devices() {
-						let item = device::BluetoothDevice::new(&device);
-						model.append(&item);
-					}
+				obj,
+				#[upgrade_or]
+				true,
+				move |item| {
+					let device = device_of(item);
+					obj.show_unnamed() || is_known(&device) || !device.name().is_empty()
 				}
-			);
+			));
+			obj.connect_show_unnamed_notify(clone!(
+				#[weak]
+				filter,
+				move |_| filter.changed(gtk4::FilterChange::Different)
+			));
 
-			add_devices(&bt);
-			bt.connect_device_added(clone!(
+			let add_device = clone!(
 				#[weak]
 				model,
-				move |_bt, device| {
-					let item = device::BluetoothDevice::new(device);
-					model.append(&item);
+				#[weak]
+				sorter,
+				#[weak]
+				filter,
+				move |device: &Device| {
+					for property in ["connected", "paired", "alias", "name"] {
+						device.connect_notify_local(
+							Some(property),
+							clone!(
+								#[weak]
+								sorter,
+								#[weak]
+								filter,
+								move |_, _| {
+									sorter.changed(gtk4::SorterChange::Different);
+									filter.changed(gtk4::FilterChange::Different);
+								}
+							),
+						);
+					}
+					model.append(&device::BluetoothDevice::new(device));
 				}
-			));
+			);
+
+			for device in bt.devices() {
+				add_device(&device);
+			}
+			bt.connect_device_added(move |_bt, device| add_device(device));
 			bt.connect_device_removed(clone!(
 				#[weak]
 				model,
@@ -126,53 +228,266 @@ mod imp {
 				}
 			));
 
-			let sort_model = SortListModel::builder().model(&model).build();
+			let filter_model = FilterListModel::new(Some(model), Some(filter));
+			let sort_model = SortListModel::new(Some(filter_model), Some(sorter));
+			sort_model.set_section_sorter(Some(&section_sorter));
 
 			let selection_model = gtk4::NoSelection::new(Some(sort_model));
 
 			self.list_view.set_model(Some(&selection_model));
 			self.list_view.set_factory(Some(&factory));
+			self.list_view.set_header_factory(Some(&create_header_factory()));
 
-			// obj.bind_property("visible", &bt, "")
-			obj.connect_notify_local(
-				Some("visible"),
-				clone!(
+			obj.connect_adapter_notify(|obj| {
+				obj.imp().restore_adapter();
+				obj.imp().bind_adapter();
+			});
+			self.adapter_dropdown.connect_selected_notify(clone!(
+				#[weak]
+				obj,
+				move |dropdown| {
+					let adapter = obj.imp().adapters.borrow().get(dropdown.selected() as usize).cloned();
+					obj.set_adapter(adapter.as_ref());
+				}
+			));
+
+			self.refresh_adapters(&bt);
+			self.bind_adapter();
+			bt.connect_adapter_added(clone!(
+				#[weak]
+				obj,
+				move |bt, _| obj.imp().refresh_adapters(bt)
+			));
+			bt.connect_adapter_removed(clone!(
+				#[weak]
+				obj,
+				move |bt, _| obj.imp().refresh_adapters(bt)
+			));
+
+			obj.connect_discoverable_notify(|obj| obj.imp().apply_discoverable());
+
+			// Don't leave the adapter scanning or discoverable behind us
+			obj.connect_closed(|obj| obj.imp().restore_adapter());
+			if let Some(app) = gio::Application::default() {
+				app.connect_shutdown(clone!(
 					#[weak]
-					bt,
-					move |obj, _| {
-						if let Some(adapter) = bt.adapter() {
-							let imp = obj.imp();
-							if obj.is_visible() {
-								// Store previous state
-								let state = AdapterState {
-									discoverable: adapter.is_discoverable(),
-									pairable:     adapter.is_pairable(),
-									discovering:  adapter.is_discovering(),
-								};
-								*imp.previous_state.borrow_mut() = Some(state);
-
-								let _ = adapter.start_discovery();
-								adapter.set_discoverable(true);
-								adapter.set_pairable(true);
-							} else {
-								// Restore previous state
-								if let Some(state) = *imp.previous_state.borrow() {
-									adapter.set_discoverable(state.discoverable);
-									adapter.set_pairable(state.pairable);
-									if !state.discovering {
-										let _ = adapter.stop_discovery();
-									}
-								}
+					obj,
+					move |_| obj.imp().restore_adapter()
+				));
+			}
+		}
+
+		fn dispose(&self) {
+			self.restore_adapter();
+		}
+	}
 
-								*imp.previous_state.borrow_mut() = None;
-							}
+	impl WidgetImpl for BluetoothPopup {}
+	impl PopoverImpl for BluetoothPopup {}
+
+	impl BluetoothPopup {
+		fn refresh_adapters(&self, bt: &Bluetooth) {
+			let adapters = bt.adapters();
+			let names = adapters
+				.iter()
+				.map(|adapter| adapter.alias().to_string())
+				.collect::<Vec<_>>();
+
+			// Keep the selection when adapters come and go, fall back to the default one
+			let current = self.obj().adapter().or_else(|| bt.adapter());
+			let selected = current
+				.and_then(|current| {
+					adapters
+						.iter()
+						.position(|adapter| adapter.address() == current.address())
+				})
+				.unwrap_or(0);
+
+			self.adapters.replace(adapters.clone());
+			self.adapter_dropdown.set_model(Some(&gtk4::StringList::new(
+				&names.iter().map(String::as_str).collect::<Vec<_>>(),
+			)));
+			self.adapter_dropdown.set_selected(selected as u32);
+			self.obj().set_has_adapter_choice(adapters.len() > 1);
+			self.obj().set_adapter(adapters.get(selected));
+		}
+
+		fn bind_adapter(&self) {
+			for binding in self.adapter_bindings.take() {
+				binding.unbind();
+			}
+
+			let obj = self.obj();
+			let adapter = obj.adapter();
+			self.power_switch.set_sensitive(adapter.is_some());
+			if let Some(adapter) = adapter {
+				let power = adapter
+					.bind_property("powered", &*self.power_switch, "active")
+					.bidirectional()
+					.sync_create()
+					.build();
+				// One way, turning it on goes through `apply_discoverable`
+				let discoverable = adapter
+					.bind_property("discoverable", &*obj, "discoverable")
+					.sync_create()
+					.build();
+				self.adapter_bindings.replace(vec![power, discoverable]);
+			}
+		}
+
+		fn start_scan(&self) {
+			let Some(adapter) = self.obj().adapter() else {
+				return;
+			};
+
+			if !adapter.is_discovering() {
+				if let Err(e) = adapter.start_discovery() {
+					eprintln!("Failed to start discovery: {}", e);
+					return;
+				}
+				self.changes.borrow_mut().discovery = Some(adapter);
+			}
+
+			let obj = self.obj();
+			obj.set_scanning(true);
+			obj.set_scan_progress(0.0);
+
+			let started = Instant::now();
+			let timer = glib::timeout_add_local(
+				Duration::from_millis(100),
+				clone!(
+					#[weak]
+					obj,
+					#[upgrade_or]
+					ControlFlow::Break,
+					move || {
+						let progress = started.elapsed().as_secs_f64() / SCAN_TIMEOUT.as_secs_f64();
+						if progress < 1.0 {
+							obj.set_scan_progress(progress);
+							return ControlFlow::Continue;
 						}
+
+						obj.imp().scan_timer.take();
+						obj.imp().stop_scan();
+						ControlFlow::Break
 					}
 				),
 			);
+			self.scan_timer.replace(Some(timer));
+		}
+
+		fn stop_scan(&self) {
+			if let Some(timer) = self.scan_timer.take() {
+				timer.remove();
+			}
+
+			let discovery = self.changes.borrow_mut().discovery.take();
+			if let Some(adapter) = discovery
+				&& let Err(e) = adapter.stop_discovery()
+			{
+				eprintln!("Failed to stop discovery: {}", e);
+			}
+
+			self.obj().set_scanning(false);
+			self.obj().set_scan_progress(0.0);
+		}
+
+		/// Makes the adapter discoverable and pairable when asked to, or undoes that.
+		fn apply_discoverable(&self) {
+			let obj = self.obj();
+			let Some(adapter) = obj.adapter() else {
+				return;
+			};
+
+			// Changes coming from the adapter itself, e.g. BlueZ' timeout
+			if obj.discoverable() == adapter.is_discoverable() {
+				if !obj.discoverable() {
+					self.changes.borrow_mut().visibility.take();
+				}
+				return;
+			}
+
+			if obj.discoverable() {
+				self.changes.borrow_mut().visibility =
+					Some((adapter.clone(), adapter.is_discoverable(), adapter.is_pairable()));
+				adapter.set_discoverable_timeout(DISCOVERABLE_TIMEOUT_S);
+				adapter.set_pairable(true);
+				adapter.set_discoverable(true);
+			} else {
+				self.restore_visibility();
+			}
+		}
+
+		fn restore_visibility(&self) {
+			let visibility = self.changes.borrow_mut().visibility.take();
+			if let Some((adapter, discoverable, pairable)) = visibility {
+				adapter.set_discoverable(discoverable);
+				adapter.set_pairable(pairable);
+			}
+		}
+
+		fn restore_adapter(&self) {
+			self.stop_scan();
+			self.restore_visibility();
 		}
 	}
 
-	impl WidgetImpl for BluetoothPopup {}
-	impl PopoverImpl for BluetoothPopup {}
+	#[gtk4::template_callbacks]
+	impl BluetoothPopup {
+		#[template_callback]
+		fn on_scan(&self) {
+			if self.obj().scanning() {
+				self.stop_scan();
+			} else {
+				self.start_scan();
+			}
+		}
+
+		#[template_callback]
+		fn scan_label(&self, scanning: bool) -> &'static str {
+			if scanning { "Stop" } else { "Scan" }
+		}
+	}
+
+	fn device_of(item: &glib::Object) -> Device {
+		item.downcast_ref::<device::BluetoothDevice>()
+			.and_then(|bt_device| bt_device.device())
+			.expect("Item is not a BluetoothDevice")
+	}
+
+	fn is_known(device: &Device) -> bool {
+		device.is_paired() || device.is_connected()
+	}
+
+	fn display_name(device: &Device) -> String {
+		device.alias().to_string()
+	}
+
+	fn create_header_factory() -> SignalListItemFactory {
+		let factory = SignalListItemFactory::new();
+
+		factory.connect_setup(|_, item| {
+			let header = item.downcast_ref::<gtk4::ListHeader>().unwrap();
+			let label = gtk4::Label::builder()
+				.halign(gtk4::Align::Start)
+				.css_classes(["bluetooth-section"])
+				.build();
+			header.set_child(Some(&label));
+		});
+
+		factory.connect_bind(|_, item| {
+			let header = item.downcast_ref::<gtk4::ListHeader>().unwrap();
+			let label = header.child().and_downcast::<gtk4::Label>().unwrap();
+
+			if let Some(item) = header.item() {
+				label.set_text(if is_known(&device_of(&item)) {
+					"My devices"
+				} else {
+					"Nearby"
+				});
+			}
+		});
+
+		factory
+	}
 }
@@ -0,0 +1,132 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use gtk4::{gdk, gio, glib};
+
+/// Maximum number of cover images kept in `$XDG_CACHE_HOME/niribar/covers`
+const MAX_CACHE_ENTRIES: usize = 128;
+
+pub async fn load_texture(url: &str) -> Result<gdk::Texture, Box<dyn Error>> {
+	let file = resolve(url).await?;
+
+	match decode(&file).await {
+		Ok(texture) => Ok(texture),
+		Err(e) => {
+			// A payload that doesn't decode would otherwise fail every load of this URL until evicted
+			if let Some(path) = file.path()
+				&& path.starts_with(cache_dir())
+				&& let Err(e) = std::fs::remove_file(&path)
+			{
+				eprintln!("Failed to remove broken cover art {:?}: {}", path, e);
+			}
+			Err(e)
+		}
+	}
+}
+
+async fn decode(file: &gio::File) -> Result<gdk::Texture, Box<dyn Error>> {
+	let image = glycin::Loader::new(file.clone()).load().await?;
+	let frame = image.next_frame().await?;
+
+	Ok(frame.texture())
+}
+
+/// Turns an `mpris:artUrl` into a local file glycin can read. `data:` and http(s) URLs are written to
+/// the cache first, keyed by the URL's hash.
+async fn resolve(url: &str) -> Result<gio::File, Box<dyn Error>> {
+	if url.starts_with('/') {
+		return Ok(gio::File::for_path(url));
+	}
+
+	match glib::Uri::peek_scheme(url).as_deref() {
+		Some("file") => Ok(gio::File::for_uri(url)),
+		Some("data") => {
+			let path = cache_path(url)?;
+			if !path.exists() {
+				let bytes = decode_data_url(url).ok_or("Malformed data: URL")?;
+				store(&path, bytes).await?;
+			}
+			Ok(gio::File::for_path(path))
+		}
+		Some("http" | "https") => {
+			let path = cache_path(url)?;
+			if !path.exists() {
+				// Goes through gvfs, which handles http(s) for us
+				let (bytes, _etag) = gio::File::for_uri(url).load_contents_future().await?;
+				store(&path, bytes.to_vec()).await?;
+			}
+			Ok(gio::File::for_path(path))
+		}
+		scheme => Err(format!("Unsupported cover art URL scheme: {scheme:?}").into()),
+	}
+}
+
+fn cache_dir() -> PathBuf {
+	glib::user_cache_dir().join("niribar").join("covers")
+}
+
+fn cache_path(url: &str) -> Result<PathBuf, Box<dyn Error>> {
+	let hash = glib::compute_checksum_for_string(glib::ChecksumType::Sha256, url).ok_or("Failed to hash URL")?;
+	Ok(cache_dir().join(hash.as_str()))
+}
+
+/// Writes a cache entry off the main thread. The entry only appears under its final name once
+/// fully written, so an interrupted write never passes for a cached cover.
+async fn store(path: &Path, bytes: Vec<u8>) -> Result<(), Box<dyn Error>> {
+	let path = path.to_path_buf();
+	gio::spawn_blocking(move || -> std::io::Result<()> {
+		std::fs::create_dir_all(cache_dir())?;
+
+		// Unique per write, two players can fetch the same cover at once
+		let temp = path.with_extension(format!("{:08x}.tmp", rand::random::<u32>()));
+		if let Err(e) = std::fs::write(&temp, bytes).and_then(|_| std::fs::rename(&temp, &path)) {
+			let _ = std::fs::remove_file(&temp);
+			return Err(e);
+		}
+
+		prune_cache();
+		Ok(())
+	})
+	.await
+	.map_err(|_| "Cover art cache writer panicked")??;
+
+	Ok(())
+}
+
+/// Evicts the oldest entries once the cache grows past [`MAX_CACHE_ENTRIES`].
+fn prune_cache() {
+	let Ok(entries) = std::fs::read_dir(cache_dir()) else {
+		return;
+	};
+
+	let mut entries = entries
+		.filter_map(Result::ok)
+		.filter_map(|entry| {
+			let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+			Some((modified, entry.path()))
+		})
+		.collect::<Vec<_>>();
+
+	if entries.len() <= MAX_CACHE_ENTRIES {
+		return;
+	}
+
+	entries.sort_by_key(|(modified, _)| *modified);
+	let excess = entries.len() - MAX_CACHE_ENTRIES;
+	for (_, path) in entries.into_iter().take(excess) {
+		if let Err(e) = std::fs::remove_file(&path) {
+			eprintln!("Failed to evict cached cover art {:?}: {}", path, e);
+		}
+	}
+}
+
+/// Decodes the payload of an RFC 2397 `data:[<mediatype>][;base64],<data>` URL.
+fn decode_data_url(url: &str) -> Option<Vec<u8>> {
+	let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+
+	if header.ends_with(";base64") {
+		Some(glib::base64_decode(data))
+	} else {
+		Some(urlencoding::decode_binary(data.as_bytes()).into_owned())
+	}
+}
@@ -0,0 +1,181 @@
+use std::path::{Path, PathBuf};
+
+use lazy_regex::regex;
+
+/// How far the `[length:]` tag of an `.lrc` file may be off from the track length (in seconds)
+/// before we consider it lyrics for a different recording.
+const LENGTH_TOLERANCE: f64 = 3.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricLine {
+	/// Start of the line in seconds, `None` for unsynced lyrics
+	pub time: Option<f64>,
+	pub text: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lyrics {
+	pub lines:  Vec<LyricLine>,
+	/// Value of the `[length:]` tag, in seconds
+	pub length: Option<f64>,
+}
+
+impl Lyrics {
+	/// Parses LRC formatted lyrics. Text without any timestamps is kept as unsynced lyrics, so this
+	/// is also fine for plain `xesam:asText` metadata.
+	pub fn parse(text: &str) -> Self {
+		let mut lyrics = Lyrics::default();
+		let mut offset = 0.0;
+
+		for line in text.lines() {
+			let line = line.trim();
+			let mut rest = line;
+			let mut times = Vec::new();
+			let mut is_metadata = false;
+
+			while let Some(captures) = regex!(r"^\[([^\]]*)\]").captures(rest) {
+				let tag = &captures[1];
+				rest = &rest[captures[0].len()..];
+
+				if let Some(time) = parse_timestamp(tag) {
+					times.push(time);
+				} else if let Some((key, value)) = tag.split_once(':') {
+					is_metadata = true;
+					match key.trim() {
+						// Positive offsets make the lyrics appear sooner
+						"offset" => offset = value.trim().parse::<f64>().unwrap_or(0.0) / 1000.0,
+						"length" => lyrics.length = parse_timestamp(value.trim()),
+						_ => (),
+					}
+				}
+			}
+
+			if !times.is_empty() {
+				let text = rest.trim();
+				lyrics.lines.extend(times.into_iter().map(|time| LyricLine {
+					time: Some(time),
+					text: text.to_string(),
+				}));
+			} else if !is_metadata {
+				// Plain text, possibly with section markers like `[Chorus]`
+				lyrics.lines.push(LyricLine {
+					time: None,
+					text: line.to_string(),
+				});
+			}
+		}
+
+		if lyrics.is_synced() {
+			lyrics.lines.retain(|line| line.time.is_some());
+			for line in &mut lyrics.lines {
+				line.time = line.time.map(|time| (time - offset).max(0.0));
+			}
+			lyrics
+				.lines
+				.sort_by(|a, b| a.time.unwrap_or(0.0).total_cmp(&b.time.unwrap_or(0.0)));
+		}
+
+		// Unsynced lyrics often come with leading/trailing blank lines
+		while lyrics
+			.lines
+			.first()
+			.is_some_and(|line| line.text.is_empty() && line.time.is_none())
+		{
+			lyrics.lines.remove(0);
+		}
+		while lyrics
+			.lines
+			.last()
+			.is_some_and(|line| line.text.is_empty() && line.time.is_none())
+		{
+			lyrics.lines.pop();
+		}
+
+		lyrics
+	}
+
+	pub fn is_synced(&self) -> bool {
+		self.lines.iter().any(|line| line.time.is_some())
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.lines.is_empty()
+	}
+
+	/// Index of the line that should be highlighted at `position` (in seconds)
+	pub fn line_at(&self, position: f64) -> Option<usize> {
+		if !self.is_synced() {
+			return None;
+		}
+
+		self.lines
+			.iter()
+			.rposition(|line| line.time.is_some_and(|time| time <= position))
+	}
+}
+
+/// Parses `mm:ss`, `mm:ss.xx` and `mm:ss:xx` timestamps into seconds.
+fn parse_timestamp(tag: &str) -> Option<f64> {
+	let captures = regex!(r"^(\d+):(\d{1,2})(?:[.:](\d{1,3}))?$").captures(tag)?;
+
+	let minutes = captures[1].parse::<f64>().ok()?;
+	let seconds = captures[2].parse::<f64>().ok()?;
+	let fraction = captures
+		.get(3)
+		.map(|f| f.as_str().parse::<f64>().unwrap_or(0.0) / 10f64.powi(f.len() as i32))
+		.unwrap_or(0.0);
+
+	Some(minutes * 60.0 + seconds + fraction)
+}
+
+/// Directory `.lrc` files are looked up in
+pub fn lyrics_dir() -> Option<PathBuf> {
+	dirs::home_dir().map(|home| home.join(".lyrics"))
+}
+
+/// Looks for `Artist - Title.lrc` (or just `Title.lrc`) in `dir`, ignoring case and punctuation.
+/// If the file has a `[length:]` tag it also has to match `length` (in seconds, `<= 0` if unknown).
+pub fn find_lrc(dir: &Path, artist: &str, title: &str, length: f64) -> Option<Lyrics> {
+	if title.is_empty() {
+		return None;
+	}
+
+	let with_artist = normalize(&format!("{artist} - {title}"));
+	let title_only = normalize(title);
+
+	let mut candidates = std::fs::read_dir(dir)
+		.ok()?
+		.filter_map(Result::ok)
+		.map(|entry| entry.path())
+		.filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("lrc")))
+		.filter_map(|path| {
+			let stem = normalize(&path.file_stem()?.to_string_lossy());
+			let rank = match stem {
+				s if !artist.is_empty() && s == with_artist => 0,
+				s if s == title_only => 1,
+				_ => return None,
+			};
+			Some((rank, path))
+		})
+		.collect::<Vec<_>>();
+	candidates.sort_by_key(|(rank, _)| *rank);
+
+	candidates.into_iter().find_map(|(_, path)| {
+		let text = std::fs::read_to_string(&path).ok()?;
+		let lyrics = Lyrics::parse(&text);
+
+		let length_matches = match lyrics.length {
+			Some(lrc_length) if length > 0.0 => (lrc_length - length).abs() <= LENGTH_TOLERANCE,
+			_ => true,
+		};
+
+		(length_matches && !lyrics.is_empty()).then_some(lyrics)
+	})
+}
+
+fn normalize(name: &str) -> String {
+	name.chars()
+		.filter(|c| c.is_alphanumeric() || *c == '-')
+		.flat_map(char::to_lowercase)
+		.collect()
+}
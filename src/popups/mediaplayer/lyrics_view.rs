@@ -0,0 +1,228 @@
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+
+use astal_mpris::prelude::PlayerExt;
+use astal_mpris::{PlaybackStatus, Player};
+use glib::{ControlFlow, Properties, clone};
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use gtk4::{CompositeTemplate, gio};
+
+use super::lyrics::{self, Lyrics};
+
+/// How often the highlighted line is re-evaluated while the view is mapped
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+glib::wrapper! {
+	pub struct LyricsView(ObjectSubclass<imp::LyricsView>)
+		@extends gtk4::Box, gtk4::Widget,
+		@implements gtk4::Accessible, gtk4::Buildable, gtk4::Constraint, gtk4::ConstraintTarget;
+}
+
+impl LyricsView {
+	pub fn new(player: &Player) -> Self {
+		glib::Object::builder().property("player", player).build()
+	}
+}
+
+mod imp {
+	use super::*;
+
+	#[derive(Default, Properties, CompositeTemplate)]
+	#[template(file = "./src/popups/mediaplayer/lyrics_view.blp")]
+	#[properties(wrapper_type = super::LyricsView)]
+	pub struct LyricsView {
+		#[property(get, construct_only)]
+		player:     RefCell<Player>,
+		#[property(get, set)]
+		has_lyrics: RefCell<bool>,
+
+		#[template_child]
+		scrolled_window: TemplateChild<gtk4::ScrolledWindow>,
+		#[template_child]
+		lines_box:       TemplateChild<gtk4::Box>,
+
+		lyrics:       RefCell<Lyrics>,
+		labels:       RefCell<Vec<gtk4::Label>>,
+		current_line: Cell<Option<usize>>,
+		/// Last position reported by the player and when we got it, used to interpolate between updates
+		position:     Cell<(f64, Option<Instant>)>,
+		tick:         RefCell<Option<glib::SourceId>>,
+		load_task:    RefCell<Option<glib::JoinHandle<()>>>,
+	}
+
+	#[glib::object_subclass]
+	impl ObjectSubclass for LyricsView {
+		type ParentType = gtk4::Box;
+		type Type = super::LyricsView;
+
+		const NAME: &'static str = "LyricsView";
+
+		fn class_init(klass: &mut Self::Class) {
+			klass.bind_template();
+		}
+
+		fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+			obj.init_template();
+		}
+	}
+
+	#[glib::derived_properties]
+	impl ObjectImpl for LyricsView {
+		fn constructed(&self) {
+			self.parent_constructed();
+
+			let obj = self.obj();
+			let player = self.player.borrow();
+
+			let reload = clone!(
+				#[weak]
+				obj,
+				move |_: &Player| obj.imp().reload()
+			);
+			reload(&player);
+			player.connect_title_notify(reload.clone());
+			player.connect_artist_notify(reload.clone());
+			player.connect_length_notify(reload.clone());
+			player.connect_lyrics_notify(reload);
+
+			let update_position = clone!(
+				#[weak]
+				obj,
+				move |player: &Player| {
+					obj.imp().position.set((player.position(), Some(Instant::now())));
+				}
+			);
+			update_position(&player);
+			player.connect_position_notify(update_position.clone());
+			player.connect_playback_status_notify(update_position);
+
+			// Only tick while actually on screen, the popup spends most of its time hidden
+			obj.connect_map(|obj| {
+				let tick = glib::timeout_add_local(
+					TICK_INTERVAL,
+					clone!(
+						#[weak]
+						obj,
+						#[upgrade_or]
+						ControlFlow::Break,
+						move || {
+							obj.imp().update_current_line();
+							ControlFlow::Continue
+						}
+					),
+				);
+				if let Some(old) = obj.imp().tick.replace(Some(tick)) {
+					old.remove();
+				}
+			});
+			obj.connect_unmap(|obj| {
+				if let Some(tick) = obj.imp().tick.take() {
+					tick.remove();
+				}
+			});
+		}
+	}
+
+	impl WidgetImpl for LyricsView {}
+	impl BoxImpl for LyricsView {}
+
+	impl LyricsView {
+		/// Prefers a matching `.lrc` file, as those are usually synced, over the player's `xesam:asText`.
+		fn reload(&self) {
+			if let Some(handle) = self.load_task.take() {
+				handle.abort();
+			}
+
+			let player = self.player.borrow();
+			let artist = player.artist().to_string();
+			let title = player.title().to_string();
+			let length = player.length();
+			let as_text = player.lyrics().to_string();
+
+			let obj = self.obj();
+			let handle = glib::spawn_future_local(clone!(
+				#[weak]
+				obj,
+				async move {
+					let from_file = gio::spawn_blocking(move || {
+						lyrics::lyrics_dir().and_then(|dir| lyrics::find_lrc(&dir, &artist, &title, length))
+					})
+					.await
+					.ok()
+					.flatten();
+
+					let lyrics = from_file.unwrap_or_else(|| Lyrics::parse(&as_text));
+					obj.imp().set_lyrics(lyrics);
+				}
+			));
+			self.load_task.replace(Some(handle));
+		}
+
+		fn set_lyrics(&self, lyrics: Lyrics) {
+			while let Some(child) = self.lines_box.first_child() {
+				self.lines_box.remove(&child);
+			}
+
+			let labels = lyrics
+				.lines
+				.iter()
+				.map(|line| {
+					let label = gtk4::Label::builder()
+						.label(&line.text)
+						.wrap(true)
+						.justify(gtk4::Justification::Center)
+						.css_classes(["lyrics-line"])
+						.build();
+					self.lines_box.append(&label);
+					label
+				})
+				.collect();
+
+			self.obj().set_has_lyrics(!lyrics.is_empty());
+			self.labels.replace(labels);
+			self.lyrics.replace(lyrics);
+			self.current_line.set(None);
+			self.scrolled_window.vadjustment().set_value(0.0);
+			self.update_current_line();
+		}
+
+		fn estimated_position(&self) -> f64 {
+			let (position, updated_at) = self.position.get();
+			let player = self.player.borrow();
+
+			match updated_at {
+				Some(updated_at) if player.playback_status() == PlaybackStatus::Playing => {
+					position + updated_at.elapsed().as_secs_f64()
+				}
+				_ => position,
+			}
+		}
+
+		fn update_current_line(&self) {
+			let line = self.lyrics.borrow().line_at(self.estimated_position());
+			let previous = self.current_line.replace(line);
+			if previous == line {
+				return;
+			}
+
+			let labels = self.labels.borrow();
+			if let Some(label) = previous.and_then(|i| labels.get(i)) {
+				label.remove_css_class("current");
+			}
+
+			let Some(label) = line.and_then(|i| labels.get(i)) else {
+				return;
+			};
+			label.add_css_class("current");
+
+			// Keep the current line vertically centred
+			if let Some(bounds) = label.compute_bounds(&*self.lines_box) {
+				let adjustment = self.scrolled_window.vadjustment();
+				let target = bounds.y() as f64 + bounds.height() as f64 / 2.0 - adjustment.page_size() / 2.0;
+				let max = (adjustment.upper() - adjustment.page_size()).max(adjustment.lower());
+				adjustment.set_value(target.clamp(adjustment.lower(), max));
+			}
+		}
+	}
+}
@@ -1,4 +1,11 @@
+mod cover_art;
+mod lyrics;
+mod lyrics_view;
+mod palette;
+mod queue;
+
 use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use astal_mpris::prelude::PlayerExt;
 use astal_mpris::{Loop, Player, Shuffle};
@@ -9,6 +16,8 @@ use gtk4::subclass::prelude::*;
 
 use crate::icons::Icon;
 
+static NEXT_PALETTE_ID: AtomicUsize = AtomicUsize::new(0);
+
 glib::wrapper! {
 	pub struct MediaPlayerPopup(ObjectSubclass<imp::MediaPlayerPopup>)
 		@extends gtk4::Popover, gtk4::Widget,
@@ -22,7 +31,7 @@ impl MediaPlayerPopup {
 			.property("shuffle-icon", Icon::Shuffle.name())
 			.property("back-icon", Icon::SkipBack.name())
 			.property("forward-icon", Icon::SkipForward.name())
-			.property("repeat-icon", Icon::Repeat.name())
+			.property("lyrics-icon", Icon::MicVocal.name())
 			.build()
 	}
 }
@@ -48,6 +57,8 @@ mod imp {
 		forward_icon: RefCell<String>,
 		#[property(get, set)]
 		repeat_icon:  RefCell<String>,
+		#[property(get, set)]
+		lyrics_icon:  RefCell<String>,
 
 		#[property(get, set)]
 		title_text:  RefCell<String>,
@@ -69,14 +80,27 @@ mod imp {
 		loop_supported:    RefCell<bool>,
 		#[property(get, set)]
 		is_repeat_active:  RefCell<bool>,
+		#[property(get, set)]
+		has_lyrics:        RefCell<bool>,
+		#[property(get, set)]
+		show_lyrics:       RefCell<bool>,
 
 		#[property(get, set)]
 		cover_image: RefCell<Option<gtk4::gdk::Paintable>>,
+		cover_task:  RefCell<Option<glib::JoinHandle<()>>>,
+
+		/// Unique CSS class the album palette is scoped to. The bar widget owning this popup adds it
+		/// too, so both pick up the same `--media-*` colours.
+		#[property(get)]
+		palette_class:    RefCell<String>,
+		palette_provider: gtk4::CssProvider,
 
 		#[template_child]
-		overlay:     TemplateChild<gtk4::Overlay>,
+		overlay:         TemplateChild<gtk4::Overlay>,
 		#[template_child]
-		content_box: TemplateChild<gtk4::Box>,
+		content_box:     TemplateChild<gtk4::Box>,
+		#[template_child]
+		lyrics_revealer: TemplateChild<gtk4::Revealer>,
 	}
 
 	#[glib::object_subclass]
@@ -105,6 +129,15 @@ mod imp {
 
 			let obj = self.obj();
 
+			let palette_class = format!("media-palette-{}", NEXT_PALETTE_ID.fetch_add(1, Ordering::Relaxed));
+			obj.add_css_class(&palette_class);
+			self.palette_class.replace(palette_class);
+			gtk4::style_context_add_provider_for_display(
+				&obj.display(),
+				&self.palette_provider,
+				gtk4::STYLE_PROVIDER_PRIORITY_USER,
+			);
+
 			let player = self.player.borrow();
 
 			player.bind_property("title", &*obj, "title-text").sync_create().build();
@@ -116,35 +149,51 @@ mod imp {
 				.sync_create()
 				.build();
 
-			// Check if shuffle is supported
-			let shuffle_status = player.shuffle_status();
-			let shuffle_supported = shuffle_status != Shuffle::Unsupported;
-			obj.set_shuffle_supported(shuffle_supported);
-
-			if shuffle_supported {
-				player
-					.bind_property("shuffle-status", &*obj, "is-shuffle-active")
-					.transform_to(|_, status: Shuffle| Some(status == Shuffle::On))
-					.transform_from(|_, shuf: bool| Some(if shuf { Shuffle::On } else { Shuffle::Off }))
-					.sync_create()
-					.bidirectional()
-					.build();
-			}
+			let lyrics_view = lyrics_view::LyricsView::new(&player);
+			lyrics_view
+				.bind_property("has-lyrics", &*obj, "has-lyrics")
+				.sync_create()
+				.build();
+			self.lyrics_revealer.set_child(Some(&lyrics_view));
 
-			// Check if loop is supported
-			let loop_status = player.loop_status();
-			let loop_supported = loop_status != Loop::Unsupported;
-			obj.set_loop_supported(loop_supported);
-
-			if loop_supported {
-				player
-					.bind_property("loop-status", &*obj, "is-repeat-active")
-					.transform_to(|_, status: Loop| Some(matches!(status, Loop::Track | Loop::Playlist)))
-					.transform_from(|_, looping: bool| Some(if looping { Loop::Playlist } else { Loop::None }))
-					.sync_create()
-					.bidirectional()
-					.build();
-			}
+			// Only shows up for players implementing the TrackList or Playlists interfaces
+			self.content_box.append(&queue::PlayerQueue::new(&player));
+
+			// Capabilities can change over the player's lifetime (e.g. switching from a track to a
+			// stream), so keep them bound instead of checking once
+			player
+				.bind_property("shuffle-status", &*obj, "shuffle-supported")
+				.transform_to(|_, status: Shuffle| Some(status != Shuffle::Unsupported))
+				.sync_create()
+				.build();
+			player
+				.bind_property("shuffle-status", &*obj, "is-shuffle-active")
+				.transform_to(|_, status: Shuffle| Some(status == Shuffle::On))
+				.transform_from(|_, shuf: bool| Some(if shuf { Shuffle::On } else { Shuffle::Off }))
+				.sync_create()
+				.bidirectional()
+				.build();
+
+			player
+				.bind_property("loop-status", &*obj, "loop-supported")
+				.transform_to(|_, status: Loop| Some(status != Loop::Unsupported))
+				.sync_create()
+				.build();
+			player
+				.bind_property("loop-status", &*obj, "is-repeat-active")
+				.transform_to(|_, status: Loop| Some(matches!(status, Loop::Track | Loop::Playlist)))
+				.sync_create()
+				.build();
+			player
+				.bind_property("loop-status", &*obj, "repeat-icon")
+				.transform_to(|_, status: Loop| {
+					Some(match status {
+						Loop::Track => Icon::Repeat1.name(),
+						_ => Icon::Repeat.name(),
+					})
+				})
+				.sync_create()
+				.build();
 
 			player
 				.bind_property("playback-status", &*obj, "play-icon")
@@ -177,48 +226,71 @@ mod imp {
 				#[weak]
 				obj,
 				move |player: &Player| {
-					let cover_art = player.cover_art();
+					let imp = obj.imp();
+					if let Some(handle) = imp.cover_task.borrow_mut().take() {
+						handle.abort();
+					}
 
-					if cover_art.is_empty() {
+					// Prefer the player's own art URL, astal's cover-art is only a local copy of it
+					let url = match player.art_url() {
+						url if url.is_empty() => player.cover_art(),
+						url => url,
+					};
+
+					if url.is_empty() {
+						obj.set_cover_image(None::<&gtk4::gdk::Paintable>);
+						imp.set_palette(None);
 						return;
 					}
 
-					let file = gtk4::gio::File::for_path(cover_art);
-					glib::spawn_future_local(async move {
-						let image = glycin::Loader::new(file).load().await;
-						let image = match image {
-							Ok(img) => img,
-							Err(_) => {
-								eprintln!("Failed to load cover art");
-								return;
-							}
-						};
-						let texture = image.next_frame().await;
-						let texture = match texture {
-							Ok(tex) => tex.texture(),
-							Err(_) => {
-								eprintln!("Failed to get texture from cover art");
-								return;
-							}
-						};
-
-						let paintable = texture.upcast::<gtk4::gdk::Paintable>();
-
-						let imp = obj.imp();
-						let mut cover_image = imp.cover_image.borrow_mut();
-						cover_image.replace(paintable);
-					});
+					let handle = glib::spawn_future_local(clone!(
+						#[weak]
+						obj,
+						async move {
+							let texture = match cover_art::load_texture(&url).await {
+								Ok(texture) => texture,
+								Err(e) => {
+									eprintln!("Failed to load cover art {}: {}", url, e);
+									obj.set_cover_image(None::<&gtk4::gdk::Paintable>);
+									obj.imp().set_palette(None);
+									return;
+								}
+							};
+
+							obj.set_cover_image(Some(texture.upcast_ref::<gtk4::gdk::Paintable>()));
+							obj.imp().set_palette(palette::Palette::from_texture(&texture));
+						}
+					));
+					*imp.cover_task.borrow_mut() = Some(handle);
 				}
 			);
 
 			cover_art(&player);
+			player.connect_art_url_notify(clone!(
+				#[strong]
+				cover_art,
+				move |player| cover_art(player)
+			));
 			player.connect_cover_art_notify(cover_art);
 		}
+
+		fn dispose(&self) {
+			gtk4::style_context_remove_provider_for_display(&self.obj().display(), &self.palette_provider);
+		}
 	}
 
 	impl WidgetImpl for MediaPlayerPopup {}
 	impl PopoverImpl for MediaPlayerPopup {}
 
+	impl MediaPlayerPopup {
+		fn set_palette(&self, palette: Option<palette::Palette>) {
+			let css = palette
+				.map(|palette| palette.to_css(&format!(".{}", self.palette_class.borrow())))
+				.unwrap_or_default();
+			self.palette_provider.load_from_string(&css);
+		}
+	}
+
 	#[gtk4::template_callbacks]
 	impl MediaPlayerPopup {
 		#[template_callback]
@@ -231,6 +303,21 @@ mod imp {
 			}
 		}
 
+		#[template_callback]
+		fn on_cycle_repeat(&self) {
+			let player = self.player.borrow();
+			let next = match player.loop_status() {
+				Loop::None => Loop::Playlist,
+				Loop::Playlist => Loop::Track,
+				Loop::Track => Loop::None,
+				_ => return,
+			};
+			player.set_loop_status(next);
+
+			// The toggle button flipped itself on click, snap it back to the actual loop state
+			self.obj().notify_is_repeat_active();
+		}
+
 		#[template_callback]
 		fn on_previous_track(&self) {
 			let player = self.player.borrow();
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use gtk4::gdk;
+
+/// Roughly how many pixels we look at per cover, regardless of its resolution
+const SAMPLE_COUNT: usize = 4096;
+
+/// Colours extracted from the current cover art, exposed to CSS as `--media-dominant`,
+/// `--media-accent` and `--media-accent-fg`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+	pub dominant: Rgb,
+	pub accent:   Rgb,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgb {
+	pub r: u8,
+	pub g: u8,
+	pub b: u8,
+}
+
+impl Palette {
+	pub fn from_texture(texture: &gdk::Texture) -> Option<Self> {
+		let mut downloader = gdk::TextureDownloader::new(texture);
+		downloader.set_format(gdk::MemoryFormat::R8g8b8a8);
+		let (bytes, stride) = downloader.download_bytes();
+
+		Self::from_rgba(&bytes, texture.width() as usize, texture.height() as usize, stride)
+	}
+
+	/// Buckets the pixels into a 4-bit-per-channel histogram. The most common bucket is the dominant
+	/// colour, the accent is the most common reasonably saturated bucket that is far enough away from it.
+	pub fn from_rgba(pixels: &[u8], width: usize, height: usize, stride: usize) -> Option<Self> {
+		if width == 0 || height == 0 {
+			return None;
+		}
+
+		let step = ((width * height) / SAMPLE_COUNT).max(1);
+
+		// bucket -> (count, summed r/g/b) so we can average the actual colours afterwards
+		let mut buckets: HashMap<u16, (u32, [u32; 3])> = HashMap::new();
+		for index in (0..width * height).step_by(step) {
+			let (x, y) = (index % width, index / width);
+			let offset = y * stride + x * 4;
+			let Some(&[r, g, b, a]) = pixels.get(offset..offset + 4) else {
+				continue;
+			};
+
+			if a < 128 {
+				continue;
+			}
+
+			let key = ((r as u16 >> 4) << 8) | ((g as u16 >> 4) << 4) | (b as u16 >> 4);
+			let entry = buckets.entry(key).or_default();
+			entry.0 += 1;
+			entry.1[0] += r as u32;
+			entry.1[1] += g as u32;
+			entry.1[2] += b as u32;
+		}
+
+		let mut colours = buckets
+			.into_values()
+			.map(|(count, [r, g, b])| {
+				let colour = Rgb {
+					r: (r / count) as u8,
+					g: (g / count) as u8,
+					b: (b / count) as u8,
+				};
+				(count, colour)
+			})
+			.collect::<Vec<_>>();
+		colours.sort_by(|a, b| b.0.cmp(&a.0));
+
+		let &(_, dominant) = colours.first()?;
+
+		let accent = colours
+			.iter()
+			.filter(|(_, colour)| colour.distance(&dominant) > 96.0)
+			.max_by(|(a_count, a), (b_count, b)| {
+				let a_score = *a_count as f32 * (0.25 + a.saturation());
+				let b_score = *b_count as f32 * (0.25 + b.saturation());
+				a_score.total_cmp(&b_score)
+			})
+			.map(|&(_, colour)| colour)
+			.unwrap_or_else(|| dominant.contrasting());
+
+		Some(Self { dominant, accent })
+	}
+
+	/// A CSS rule defining the palette as custom properties on everything matching `selector`.
+	pub fn to_css(self, selector: &str) -> String {
+		format!(
+			"{selector} {{ --media-dominant: {}; --media-accent: {}; --media-accent-fg: {}; }}",
+			self.dominant,
+			self.accent,
+			self.accent.contrasting(),
+		)
+	}
+}
+
+impl Rgb {
+	fn saturation(&self) -> f32 {
+		let max = self.r.max(self.g).max(self.b) as f32;
+		let min = self.r.min(self.g).min(self.b) as f32;
+		if max == 0.0 { 0.0 } else { (max - min) / max }
+	}
+
+	/// Relative luminance as per WCAG, in `0.0..=1.0`
+	fn luminance(&self) -> f32 {
+		let channel = |c: u8| {
+			let c = c as f32 / 255.0;
+			if c <= 0.03928 {
+				c / 12.92
+			} else {
+				((c + 0.055) / 1.055).powf(2.4)
+			}
+		};
+		0.2126 * channel(self.r) + 0.7152 * channel(self.g) + 0.0722 * channel(self.b)
+	}
+
+	fn distance(&self, other: &Rgb) -> f32 {
+		let dr = self.r as f32 - other.r as f32;
+		let dg = self.g as f32 - other.g as f32;
+		let db = self.b as f32 - other.b as f32;
+		(dr * dr + dg * dg + db * db).sqrt()
+	}
+
+	/// Black or white, whichever is more readable on top of this colour
+	fn contrasting(&self) -> Rgb {
+		if self.luminance() > 0.179 {
+			Rgb { r: 0, g: 0, b: 0 }
+		} else {
+			Rgb { r: 255, g: 255, b: 255 }
+		}
+	}
+}
+
+impl std::fmt::Display for Rgb {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "rgb({}, {}, {})", self.r, self.g, self.b)
+	}
+}
@@ -0,0 +1,382 @@
+use std::cell::RefCell;
+
+use astal_mpris::Player;
+use astal_mpris::prelude::PlayerExt;
+use glib::variant::ObjectPath;
+use glib::{Properties, Variant, VariantDict, clone};
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use gtk4::{CompositeTemplate, gio};
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const TRACKLIST_INTERFACE: &str = "org.mpris.MediaPlayer2.TrackList";
+const PLAYLISTS_INTERFACE: &str = "org.mpris.MediaPlayer2.Playlists";
+
+/// Upper bound of playlists requested via `GetPlaylists`
+const MAX_PLAYLISTS: u32 = 100;
+const CALL_TIMEOUT_MS: i32 = 2000;
+
+glib::wrapper! {
+	pub struct PlayerQueue(ObjectSubclass<imp::PlayerQueue>)
+		@extends gtk4::Box, gtk4::Widget,
+		@implements gtk4::Accessible, gtk4::Buildable, gtk4::Constraint, gtk4::ConstraintTarget;
+}
+
+impl PlayerQueue {
+	pub fn new(player: &Player) -> Self {
+		glib::Object::builder().property("player", player).build()
+	}
+}
+
+mod imp {
+	use super::*;
+
+	#[derive(Default, Properties, CompositeTemplate)]
+	#[template(file = "./src/popups/mediaplayer/queue.blp")]
+	#[properties(wrapper_type = super::PlayerQueue)]
+	pub struct PlayerQueue {
+		#[property(get, construct_only)]
+		player: RefCell<Player>,
+
+		#[property(get, set)]
+		has_tracklist: RefCell<bool>,
+		#[property(get, set)]
+		has_playlists: RefCell<bool>,
+		/// Whether the player implements either interface, the whole widget hides otherwise
+		#[property(get, set)]
+		available:     RefCell<bool>,
+
+		#[template_child]
+		tracks_list:    TemplateChild<gtk4::ListBox>,
+		#[template_child]
+		playlists_list: TemplateChild<gtk4::ListBox>,
+
+		tracklist:    RefCell<Option<gio::DBusProxy>>,
+		playlists:    RefCell<Option<gio::DBusProxy>>,
+		track_ids:    RefCell<Vec<String>>,
+		playlist_ids: RefCell<Vec<String>>,
+	}
+
+	#[glib::object_subclass]
+	impl ObjectSubclass for PlayerQueue {
+		type ParentType = gtk4::Box;
+		type Type = super::PlayerQueue;
+
+		const NAME: &'static str = "PlayerQueue";
+
+		fn class_init(klass: &mut Self::Class) {
+			klass.bind_template();
+			klass.bind_template_callbacks();
+		}
+
+		fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+			obj.init_template();
+		}
+	}
+
+	#[glib::derived_properties]
+	impl ObjectImpl for PlayerQueue {
+		fn constructed(&self) {
+			self.parent_constructed();
+
+			let obj = self.obj();
+			let bus_name = self.player.borrow().bus_name().to_string();
+
+			glib::spawn_future_local(clone!(
+				#[weak]
+				obj,
+				async move {
+					let tracklist = proxy(&bus_name, TRACKLIST_INTERFACE).await;
+					let playlists = proxy(&bus_name, PLAYLISTS_INTERFACE).await;
+
+					// Proxies are created regardless of whether the player implements the interface,
+					// but the properties are only cached if it actually does
+					let imp = obj.imp();
+					if let Some(tracklist) = tracklist.filter(|p| p.cached_property("Tracks").is_some()) {
+						imp.watch_tracklist(&tracklist);
+						imp.tracklist.replace(Some(tracklist));
+						obj.set_has_tracklist(true);
+					}
+					if let Some(playlists) = playlists.filter(|p| p.cached_property("PlaylistCount").is_some()) {
+						imp.watch_playlists(&playlists);
+						imp.playlists.replace(Some(playlists));
+						obj.set_has_playlists(true);
+					}
+
+					obj.set_available(obj.has_tracklist() || obj.has_playlists());
+				}
+			));
+
+			self.player.borrow().connect_trackid_notify(clone!(
+				#[weak]
+				obj,
+				move |_| obj.imp().highlight_current_track()
+			));
+		}
+	}
+
+	impl WidgetImpl for PlayerQueue {}
+	impl BoxImpl for PlayerQueue {}
+
+	#[gtk4::template_callbacks]
+	impl PlayerQueue {
+		#[template_callback]
+		fn on_track_activated(&self, row: &gtk4::ListBoxRow) {
+			let Some(track_id) = self.track_ids.borrow().get(row.index() as usize).cloned() else {
+				return;
+			};
+			if let Some(tracklist) = self.tracklist.borrow().clone() {
+				call(tracklist, "GoTo", track_id);
+			}
+		}
+
+		#[template_callback]
+		fn on_playlist_activated(&self, row: &gtk4::ListBoxRow) {
+			let Some(playlist_id) = self.playlist_ids.borrow().get(row.index() as usize).cloned() else {
+				return;
+			};
+			if let Some(playlists) = self.playlists.borrow().clone() {
+				call(playlists, "ActivatePlaylist", playlist_id);
+			}
+		}
+	}
+
+	impl PlayerQueue {
+		fn watch_tracklist(&self, tracklist: &gio::DBusProxy) {
+			let obj = self.obj();
+
+			// TrackListReplaced, TrackAdded, TrackRemoved and TrackMetadataChanged all warrant a reload
+			tracklist.connect_g_signal(
+				None,
+				clone!(
+					#[weak]
+					obj,
+					move |_, _, _, _| obj.imp().reload_tracks()
+				),
+			);
+			self.reload_tracks();
+		}
+
+		fn watch_playlists(&self, playlists: &gio::DBusProxy) {
+			let obj = self.obj();
+
+			playlists.connect_g_signal(
+				None,
+				clone!(
+					#[weak]
+					obj,
+					move |_, _, _, _| obj.imp().reload_playlists()
+				),
+			);
+			playlists.connect_g_properties_changed(clone!(
+				#[weak]
+				obj,
+				move |_, _, _| obj.imp().reload_playlists()
+			));
+			self.reload_playlists();
+		}
+
+		fn reload_tracks(&self) {
+			let Some(tracklist) = self.tracklist.borrow().clone() else {
+				return;
+			};
+			let obj = self.obj();
+
+			glib::spawn_future_local(clone!(
+				#[weak]
+				obj,
+				async move {
+					// `Tracks` is not announced via PropertiesChanged, so always fetch it fresh
+					let tracks = match tracklist
+						.call_future(
+							"org.freedesktop.DBus.Properties.Get",
+							Some(&(TRACKLIST_INTERFACE, "Tracks").to_variant()),
+							gio::DBusCallFlags::NONE,
+							CALL_TIMEOUT_MS,
+						)
+						.await
+					{
+						Ok(reply) => reply.child_value(0).as_variant().unwrap_or(reply),
+						Err(e) => {
+							eprintln!("Failed to get track list: {}", e);
+							return;
+						}
+					};
+
+					let metadata = match tracklist
+						.call_future(
+							"GetTracksMetadata",
+							Some(&Variant::tuple_from_iter([tracks])),
+							gio::DBusCallFlags::NONE,
+							CALL_TIMEOUT_MS,
+						)
+						.await
+					{
+						Ok(reply) => reply.child_value(0),
+						Err(e) => {
+							eprintln!("Failed to get track metadata: {}", e);
+							return;
+						}
+					};
+
+					let imp = obj.imp();
+					let mut track_ids = Vec::new();
+					while let Some(row) = imp.tracks_list.first_child() {
+						imp.tracks_list.remove(&row);
+					}
+
+					for track in metadata.iter() {
+						let track = VariantDict::new(Some(&track));
+						let Some(track_id) = track
+							.lookup_value("mpris:trackid", None)
+							.and_then(|id| id.str().map(String::from))
+						else {
+							continue;
+						};
+
+						let title = track.lookup::<String>("xesam:title").ok().flatten().unwrap_or_default();
+						let artists = track
+							.lookup::<Vec<String>>("xesam:artist")
+							.ok()
+							.flatten()
+							.unwrap_or_default();
+
+						imp.tracks_list.append(&queue_row(&title, &artists.join(", ")));
+						track_ids.push(track_id);
+					}
+
+					imp.track_ids.replace(track_ids);
+					imp.highlight_current_track();
+				}
+			));
+		}
+
+		fn reload_playlists(&self) {
+			let Some(playlists) = self.playlists.borrow().clone() else {
+				return;
+			};
+			let obj = self.obj();
+
+			glib::spawn_future_local(clone!(
+				#[weak]
+				obj,
+				async move {
+					let ordering = playlists
+						.cached_property("Orderings")
+						.and_then(|orderings| orderings.get::<Vec<String>>())
+						.and_then(|orderings| orderings.into_iter().next())
+						.unwrap_or_else(|| "Alphabetical".to_string());
+
+					let reply = match playlists
+						.call_future(
+							"GetPlaylists",
+							Some(&(0u32, MAX_PLAYLISTS, ordering, false).to_variant()),
+							gio::DBusCallFlags::NONE,
+							CALL_TIMEOUT_MS,
+						)
+						.await
+					{
+						Ok(reply) => reply,
+						Err(e) => {
+							eprintln!("Failed to get playlists: {}", e);
+							return;
+						}
+					};
+
+					// ActivePlaylist is `(b(oss))`, the bool tells whether the struct is valid
+					let active_id = playlists
+						.cached_property("ActivePlaylist")
+						.and_then(|active| active.get::<(bool, (ObjectPath, String, String))>())
+						.and_then(|(valid, (id, ..))| valid.then(|| id.as_str().to_string()));
+
+					let imp = obj.imp();
+					let mut playlist_ids = Vec::new();
+					while let Some(row) = imp.playlists_list.first_child() {
+						imp.playlists_list.remove(&row);
+					}
+
+					let Some(entries) = reply.child_value(0).get::<Vec<(ObjectPath, String, String)>>() else {
+						return;
+					};
+					for (id, name, _icon) in entries {
+						let row = queue_row(&name, "");
+						if active_id.as_deref() == Some(id.as_str()) {
+							row.add_css_class("current");
+						}
+						imp.playlists_list.append(&row);
+						playlist_ids.push(id.as_str().to_string());
+					}
+
+					imp.playlist_ids.replace(playlist_ids);
+				}
+			));
+		}
+
+		fn highlight_current_track(&self) {
+			let current = self.player.borrow().trackid().to_string();
+
+			for (index, track_id) in self.track_ids.borrow().iter().enumerate() {
+				if let Some(row) = self.tracks_list.row_at_index(index as i32) {
+					if *track_id == current {
+						row.add_css_class("current");
+					} else {
+						row.remove_css_class("current");
+					}
+				}
+			}
+		}
+	}
+
+	fn queue_row(title: &str, subtitle: &str) -> gtk4::ListBoxRow {
+		let label = gtk4::Label::builder()
+			.label(if subtitle.is_empty() {
+				title.to_string()
+			} else {
+				format!("{title} — {subtitle}")
+			})
+			.halign(gtk4::Align::Start)
+			.ellipsize(gtk4::pango::EllipsizeMode::End)
+			.max_width_chars(40)
+			.build();
+
+		gtk4::ListBoxRow::builder()
+			.child(&label)
+			.css_classes(["media-player-queue-item"])
+			.build()
+	}
+
+	async fn proxy(bus_name: &str, interface: &str) -> Option<gio::DBusProxy> {
+		gio::DBusProxy::for_bus_future(
+			gio::BusType::Session,
+			gio::DBusProxyFlags::NONE,
+			None,
+			bus_name,
+			MPRIS_PATH,
+			interface,
+		)
+		.await
+		.inspect_err(|e| eprintln!("Failed to create {} proxy for {}: {}", interface, bus_name, e))
+		.ok()
+	}
+
+	/// Calls a method taking a single object path (`GoTo`, `ActivatePlaylist`) without waiting for it.
+	fn call(proxy: gio::DBusProxy, method: &'static str, path: String) {
+		let Ok(path) = ObjectPath::try_from(path) else {
+			return;
+		};
+
+		glib::spawn_future_local(async move {
+			if let Err(e) = proxy
+				.call_future(
+					method,
+					Some(&(path,).to_variant()),
+					gio::DBusCallFlags::NONE,
+					CALL_TIMEOUT_MS,
+				)
+				.await
+			{
+				eprintln!("Failed to call {}: {}", method, e);
+			}
+		});
+	}
+}
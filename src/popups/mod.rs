@@ -2,4 +2,5 @@ pub mod bluetooth;
 pub mod clock;
 pub mod launcher;
 pub mod mediaplayer;
+pub mod network;
 pub mod volume;
@@ -0,0 +1,88 @@
+use std::cell::RefCell;
+
+use astal_network::AccessPoint;
+use glib::{GString, Properties};
+use gtk4::CompositeTemplate;
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+
+use super::wifi_icon;
+use crate::icons::Icon;
+
+glib::wrapper! {
+	pub struct AccessPointRow(ObjectSubclass<imp::AccessPointRow>)
+		@extends gtk4::Button, gtk4::Widget,
+		@implements gtk4::Accessible, gtk4::Actionable, gtk4::Buildable, gtk4::Constraint, gtk4::ConstraintTarget;
+}
+
+impl AccessPointRow {
+	pub fn new(access_point: &AccessPoint, active: bool, saved: bool) -> Self {
+		let obj: Self = glib::Object::builder()
+			.property("access-point", access_point)
+			.property("saved", saved)
+			.property("lock-icon", Icon::Lock.name())
+			.build();
+
+		access_point
+			.bind_property("strength", &obj, "strength-icon")
+			.transform_to(|_, strength: u8| Some(wifi_icon(strength).name()))
+			.sync_create()
+			.build();
+
+		if active {
+			obj.add_css_class("active");
+		}
+		obj
+	}
+}
+
+mod imp {
+	use super::*;
+
+	#[derive(Default, Properties, CompositeTemplate)]
+	#[template(file = "./src/popups/network/access_point.blp")]
+	#[properties(wrapper_type = super::AccessPointRow)]
+	pub struct AccessPointRow {
+		#[property(get, construct_only)]
+		access_point: RefCell<Option<AccessPoint>>,
+		/// A connection profile for this network exists, so no password is needed
+		#[property(get, construct_only)]
+		saved:        RefCell<bool>,
+
+		#[property(get, set)]
+		strength_icon: RefCell<String>,
+		#[property(get, set)]
+		lock_icon:     RefCell<String>,
+	}
+
+	#[glib::object_subclass]
+	impl ObjectSubclass for AccessPointRow {
+		type ParentType = gtk4::Button;
+		type Type = super::AccessPointRow;
+
+		const NAME: &'static str = "AccessPointRow";
+
+		fn class_init(klass: &mut Self::Class) {
+			klass.bind_template();
+			klass.bind_template_callbacks();
+		}
+
+		fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+			obj.init_template();
+		}
+	}
+
+	#[glib::derived_properties]
+	impl ObjectImpl for AccessPointRow {}
+
+	impl WidgetImpl for AccessPointRow {}
+	impl ButtonImpl for AccessPointRow {}
+
+	#[gtk4::template_callbacks]
+	impl AccessPointRow {
+		#[template_callback]
+		fn format_strength(&self, strength: u8) -> GString {
+			format!("{}%", strength).into()
+		}
+	}
+}
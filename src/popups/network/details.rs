@@ -0,0 +1,81 @@
+use gtk4::prelude::*;
+use libnm_api::prelude::*;
+
+/// `(label, value)` pairs describing the addressing and link of a device.
+pub fn device_details(device: &libnm_api::Device) -> Vec<(&'static str, String)> {
+	let mut details = Vec::new();
+
+	// `ip4_config()`/`ip6_config()` assume a config, the properties are `NULL` while unconfigured
+	for (label, property) in [("IPv4", "ip4-config"), ("IPv6", "ip6-config")] {
+		let Some(config) = device.property::<Option<libnm_api::IPConfig>>(property) else {
+			continue;
+		};
+		for address in config.addresses() {
+			details.push((label, format!("{}/{}", address.address(), address.prefix())));
+		}
+		let gateway = config.gateway();
+		if !gateway.is_empty() {
+			details.push(("Gateway", gateway.to_string()));
+		}
+		for nameserver in config.nameservers() {
+			details.push(("DNS", nameserver.to_string()));
+		}
+	}
+
+	let speed = if let Some(ethernet) = device.downcast_ref::<libnm_api::DeviceEthernet>() {
+		ethernet.speed()
+	} else if let Some(wifi) = device.downcast_ref::<libnm_api::DeviceWifi>() {
+		// Reported in kb/s
+		wifi.bitrate() / 1000
+	} else {
+		0
+	};
+	if speed > 0 {
+		details.push(("Speed", format!("{} Mb/s", speed)));
+	}
+
+	let mac = device.hw_address();
+	if !mac.is_empty() {
+		details.push(("MAC", mac.to_string()));
+	}
+
+	details
+}
+
+/// Row showing one detail that copies its value to the clipboard when clicked.
+pub fn detail_row(label: &str, value: &str) -> gtk4::Button {
+	let content = gtk4::Box::builder()
+		.orientation(gtk4::Orientation::Horizontal)
+		.spacing(8)
+		.build();
+	content.append(
+		&gtk4::Label::builder()
+			.label(label)
+			.halign(gtk4::Align::Start)
+			.css_classes(["network-detail-label"])
+			.build(),
+	);
+	content.append(
+		&gtk4::Label::builder()
+			.label(value)
+			.hexpand(true)
+			.halign(gtk4::Align::End)
+			.ellipsize(gtk4::pango::EllipsizeMode::Start)
+			.build(),
+	);
+
+	let button = gtk4::Button::builder()
+		.child(&content)
+		.tooltip_text("Click to copy")
+		.css_classes(["network-detail"])
+		.build();
+
+	let value = value.to_string();
+	button.connect_clicked(move |button| {
+		button.clipboard().set_text(&value);
+		button.set_tooltip_text(Some("Copied"));
+	});
+	button.connect_unmap(|button| button.set_tooltip_text(Some("Click to copy")));
+
+	button
+}
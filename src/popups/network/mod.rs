@@ -0,0 +1,508 @@
+mod access_point;
+mod details;
+mod sparkline;
+mod throughput;
+mod vpn;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use access_point::AccessPointRow;
+use astal_network::prelude::*;
+use astal_network::{AccessPoint, Network, Wifi};
+use glib::{GString, Properties, clone};
+use gtk4::CompositeTemplate;
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use libnm_api::prelude::*;
+use sparkline::Sparkline;
+pub use throughput::{RateUnit, Throughput};
+pub use vpn::active_vpns;
+
+use crate::icons::Icon;
+
+glib::wrapper! {
+	pub struct NetworkPopup(ObjectSubclass<imp::NetworkPopup>)
+		@extends gtk4::Popover, gtk4::Widget,
+		@implements gtk4::Accessible, gtk4::Buildable, gtk4::Constraint, gtk4::ConstraintTarget, gtk4::ShortcutManager, gtk4::Native;
+}
+
+impl NetworkPopup {
+	pub fn new(throughput: Option<&Throughput>) -> Self {
+		glib::Object::builder()
+			.property("scan-icon", Icon::RefreshCw.name())
+			.property("throughput", throughput)
+			.build()
+	}
+}
+
+/// Graded Wi-Fi icon for a signal strength in percent.
+pub fn wifi_icon(strength: u8) -> Icon {
+	match strength {
+		75.. => Icon::Wifi,
+		50..75 => Icon::WifiHigh,
+		25..50 => Icon::WifiLow,
+		_ => Icon::WifiZero,
+	}
+}
+
+mod imp {
+	use super::*;
+
+	#[derive(Default, Properties, CompositeTemplate)]
+	#[template(file = "./src/popups/network/network.blp")]
+	#[properties(wrapper_type = super::NetworkPopup)]
+	pub struct NetworkPopup {
+		#[property(get, construct_only)]
+		throughput:     RefCell<Option<Throughput>>,
+		#[property(get, set)]
+		has_throughput: RefCell<bool>,
+		#[property(get, set)]
+		has_vpns:       RefCell<bool>,
+		#[property(get, set)]
+		has_details:    RefCell<bool>,
+		/// Interface of the primary device
+		#[property(get, set)]
+		details_title:  RefCell<String>,
+
+		#[property(get, set)]
+		wifi:            RefCell<Option<Wifi>>,
+		#[property(get, set)]
+		has_wifi:        RefCell<bool>,
+		#[property(get, set)]
+		scan_icon:       RefCell<String>,
+		/// SSID of the network we're asking the password for
+		#[property(get, set)]
+		password_ssid:   RefCell<String>,
+		#[property(get, set)]
+		asking_password: RefCell<bool>,
+		/// Why connecting or disconnecting last failed
+		#[property(get, set)]
+		error_message:   RefCell<String>,
+		#[property(get, set)]
+		has_error:       RefCell<bool>,
+
+		#[template_child]
+		wifi_switch:        TemplateChild<gtk4::Switch>,
+		#[template_child]
+		password_entry:     TemplateChild<gtk4::PasswordEntry>,
+		#[template_child]
+		access_points_list: TemplateChild<gtk4::ListBox>,
+		#[template_child]
+		throughput_box:     TemplateChild<gtk4::Box>,
+		#[template_child]
+		vpn_list:           TemplateChild<gtk4::ListBox>,
+		#[template_child]
+		details_list:       TemplateChild<gtk4::ListBox>,
+
+		/// Access point the password prompt is for
+		pending_access_point: RefCell<Option<AccessPoint>>,
+	}
+
+	#[glib::object_subclass]
+	impl ObjectSubclass for NetworkPopup {
+		type ParentType = gtk4::Popover;
+		type Type = super::NetworkPopup;
+
+		const NAME: &'static str = "NetworkPopup";
+
+		fn class_init(klass: &mut Self::Class) {
+			klass.bind_template();
+			klass.bind_template_callbacks();
+		}
+
+		fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+			obj.init_template();
+		}
+	}
+
+	#[glib::derived_properties]
+	impl ObjectImpl for NetworkPopup {
+		fn constructed(&self) {
+			self.parent_constructed();
+
+			let obj = self.obj();
+
+			if let Some(throughput) = obj.throughput() {
+				self.add_throughput(&throughput);
+			}
+
+			if let Some(network) = Network::default() {
+				self.watch_vpns(&network.client());
+
+				network.connect_primary_notify(clone!(
+					#[weak]
+					obj,
+					move |_| obj.imp().refresh_details()
+				));
+			}
+			// Addresses and link speed aren't worth watching while closed
+			obj.connect_map(|obj| obj.imp().refresh_details());
+
+			let Some(wifi) = Network::default().and_then(|nw| nw.wifi()) else {
+				return;
+			};
+			obj.set_wifi(Some(&wifi));
+			obj.set_has_wifi(true);
+
+			wifi.bind_property("enabled", &*self.wifi_switch, "active")
+				.bidirectional()
+				.sync_create()
+				.build();
+
+			self.refresh_access_points();
+			for property in ["access-points", "active-access-point"] {
+				wifi.connect_notify_local(
+					Some(property),
+					clone!(
+						#[weak]
+						obj,
+						move |_, _| obj.imp().refresh_access_points()
+					),
+				);
+			}
+
+			// Scan whenever the popup is opened, results trickle in through `access-points`
+			obj.connect_map(|obj| {
+				if let Some(wifi) = obj.wifi()
+					&& wifi.is_enabled()
+				{
+					wifi.scan();
+				}
+			});
+			obj.connect_closed(|obj| {
+				obj.imp().cancel_password_prompt();
+				obj.imp().set_error(None);
+			});
+		}
+	}
+
+	impl WidgetImpl for NetworkPopup {}
+	impl PopoverImpl for NetworkPopup {}
+
+	#[gtk4::template_callbacks]
+	impl NetworkPopup {
+		#[template_callback]
+		fn on_scan(&self) {
+			if let Some(wifi) = self.obj().wifi() {
+				wifi.scan();
+			}
+		}
+
+		#[template_callback]
+		fn password_prompt(&self, ssid: &str) -> GString {
+			format!("Password for {}", ssid).into()
+		}
+
+		#[template_callback]
+		fn on_password_submit(&self) {
+			let password = self.password_entry.text().to_string();
+			if password.is_empty() {
+				return;
+			}
+
+			if let Some(access_point) = self.pending_access_point.take() {
+				self.activate_access_point(&access_point, Some(password));
+			}
+			self.cancel_password_prompt();
+		}
+
+		#[template_callback]
+		fn on_password_cancel(&self) {
+			self.cancel_password_prompt();
+		}
+	}
+
+	impl NetworkPopup {
+		fn add_throughput(&self, throughput: &Throughput) {
+			let rates = gtk4::Label::builder().halign(gtk4::Align::Start).build();
+			throughput.connect_transmitted_notify(clone!(
+				#[weak]
+				rates,
+				move |throughput| {
+					rates.set_label(&format!(
+						"Down {} · Up {}",
+						throughput.format_rate(throughput.received()),
+						throughput.format_rate(throughput.transmitted())
+					));
+				}
+			));
+
+			self.throughput_box.append(&Sparkline::new(throughput));
+			self.throughput_box.append(&rates);
+			self.obj().set_has_throughput(true);
+		}
+
+		fn watch_vpns(&self, client: &libnm_api::Client) {
+			let obj = self.obj();
+			self.refresh_vpns(client);
+
+			client.connect_active_connections_notify(clone!(
+				#[weak]
+				obj,
+				move |client| obj.imp().refresh_vpns(client)
+			));
+			client.connect_connection_added(clone!(
+				#[weak]
+				obj,
+				move |client, _| obj.imp().refresh_vpns(client)
+			));
+			client.connect_connection_removed(clone!(
+				#[weak]
+				obj,
+				move |client, _| obj.imp().refresh_vpns(client)
+			));
+		}
+
+		fn refresh_vpns(&self, client: &libnm_api::Client) {
+			while let Some(row) = self.vpn_list.first_child() {
+				self.vpn_list.remove(&row);
+			}
+
+			let active = vpn::active_vpns(client);
+			let profiles = vpn::vpn_profiles(client);
+			self.obj().set_has_vpns(!profiles.is_empty());
+
+			for profile in profiles {
+				let active = active
+					.iter()
+					.find(|connection| profile.uuid() == connection.uuid())
+					.cloned();
+				self.vpn_list.append(&vpn::vpn_row(client, &profile, active));
+			}
+		}
+
+		fn refresh_details(&self) {
+			while let Some(row) = self.details_list.first_child() {
+				self.details_list.remove(&row);
+			}
+
+			let Some(device) = Network::default().and_then(|nw| primary_device(&nw)) else {
+				self.obj().set_has_details(false);
+				return;
+			};
+
+			let details = details::device_details(&device);
+			for (label, value) in &details {
+				self.details_list.append(&details::detail_row(label, value));
+			}
+			self.obj().set_details_title(device.iface().as_str());
+			self.obj().set_has_details(!details.is_empty());
+		}
+
+		fn refresh_access_points(&self) {
+			let Some(wifi) = self.obj().wifi() else {
+				return;
+			};
+
+			while let Some(row) = self.access_points_list.first_child() {
+				self.access_points_list.remove(&row);
+			}
+
+			// Every band and mesh node shows up as its own access point, keep the strongest per SSID
+			let mut by_ssid = HashMap::<String, AccessPoint>::new();
+			for access_point in wifi.access_points() {
+				let Some(ssid) = access_point.ssid().filter(|ssid| !ssid.is_empty()) else {
+					continue;
+				};
+				match by_ssid.get(ssid.as_str()) {
+					Some(existing) if existing.strength() >= access_point.strength() => (),
+					_ => {
+						by_ssid.insert(ssid.to_string(), access_point);
+					}
+				}
+			}
+
+			let active_ssid = wifi.active_access_point().and_then(|ap| ap.ssid());
+			let mut access_points = by_ssid
+				.into_values()
+				.map(|access_point| {
+					let active = access_point.ssid() == active_ssid;
+					let saved = saved_connection(&wifi, &access_point).is_some();
+					(access_point, active, saved)
+				})
+				.collect::<Vec<_>>();
+
+			// Active first, then networks we can join right away, then by signal
+			access_points.sort_by(|(a, a_active, a_saved), (b, b_active, b_saved)| {
+				b_active
+					.cmp(a_active)
+					.then_with(|| b_saved.cmp(a_saved))
+					.then_with(|| b.strength().cmp(&a.strength()))
+			});
+
+			let obj = self.obj();
+			for (access_point, active, saved) in access_points {
+				let row = AccessPointRow::new(&access_point, active, saved);
+				row.connect_clicked(clone!(
+					#[weak]
+					obj,
+					#[weak]
+					access_point,
+					move |_| obj.imp().on_access_point_clicked(&access_point, active, saved)
+				));
+				self.access_points_list.append(&row);
+			}
+		}
+
+		fn on_access_point_clicked(&self, access_point: &AccessPoint, active: bool, saved: bool) {
+			self.set_error(None);
+			let key_mgmt = self
+				.obj()
+				.wifi()
+				.and_then(|wifi| nm_access_point(&wifi, access_point))
+				.map(|nm_access_point| key_mgmt(&nm_access_point));
+
+			if active {
+				self.deactivate();
+			} else if saved || matches!(key_mgmt, Some(Ok(None))) {
+				self.activate_access_point(access_point, None);
+			} else if let Some(Err(unsupported)) = key_mgmt {
+				self.set_error(Some(unsupported.to_string()));
+			} else {
+				let ssid = access_point.ssid().unwrap_or_default();
+				self.pending_access_point.replace(Some(access_point.clone()));
+				self.password_entry.set_text("");
+				self.obj().set_password_ssid(ssid.as_str());
+				self.obj().set_asking_password(true);
+				self.password_entry.grab_focus();
+			}
+		}
+
+		fn cancel_password_prompt(&self) {
+			self.pending_access_point.take();
+			self.password_entry.set_text("");
+			self.obj().set_asking_password(false);
+		}
+
+		fn set_error(&self, message: Option<String>) {
+			let obj = self.obj();
+			obj.set_has_error(message.is_some());
+			obj.set_error_message(message.unwrap_or_default());
+		}
+
+		/// Activates the saved profile for `access_point`, or creates one. NetworkManager fills in
+		/// everything but the password from the access point itself.
+		fn activate_access_point(&self, access_point: &AccessPoint, password: Option<String>) {
+			let obj = self.obj();
+			let (Some(network), Some(wifi)) = (Network::default(), obj.wifi()) else {
+				return;
+			};
+			let client = network.client();
+			let device = wifi.device();
+			let Some(nm_access_point) = nm_access_point(&wifi, access_point) else {
+				return;
+			};
+			let saved = saved_connection(&wifi, access_point);
+			let ssid = access_point.ssid().unwrap_or_default();
+
+			glib::spawn_future_local(clone!(
+				#[weak]
+				obj,
+				async move {
+					let path = nm_access_point.path();
+					let result = match (saved, password) {
+						(Some(connection), None) => client
+							.activate_connection_future(Some(&connection), Some(&device), Some(&path))
+							.await
+							.map(|_| ()),
+						(_, password) => {
+							let key_mgmt = key_mgmt(&nm_access_point).ok().flatten().unwrap_or("wpa-psk");
+							let partial = password.map(|password| {
+								let security = libnm_api::SettingWirelessSecurity::new();
+								security.set_property("key-mgmt", key_mgmt);
+								security.set_property("psk", password);
+
+								let connection = libnm_api::SimpleConnection::new();
+								connection.add_setting(security);
+								connection
+							});
+							client
+								.add_and_activate_connection_future(partial.as_ref(), Some(&device), Some(&path))
+								.await
+								.map(|_| ())
+						}
+					};
+
+					if let Err(e) = result {
+						eprintln!("Failed to connect to {:?}: {}", ssid, e);
+						obj.imp()
+							.set_error(Some(format!("Couldn't connect to {}: {}", ssid, e.message())));
+					}
+				}
+			));
+		}
+
+		fn deactivate(&self) {
+			let (Some(network), Some(wifi)) = (Network::default(), self.obj().wifi()) else {
+				return;
+			};
+			let Some(active_connection) = wifi.active_connection() else {
+				return;
+			};
+			let client = network.client();
+
+			let obj = self.obj();
+			glib::spawn_future_local(clone!(
+				#[weak]
+				obj,
+				async move {
+					if let Err(e) = client.deactivate_connection_future(&active_connection).await {
+						eprintln!("Failed to disconnect: {}", e);
+						obj.imp()
+							.set_error(Some(format!("Couldn't disconnect: {}", e.message())));
+					}
+				}
+			));
+		}
+	}
+
+	fn primary_device(nw: &Network) -> Option<libnm_api::Device> {
+		match nw.primary() {
+			astal_network::Primary::Wired => nw.wired().map(|wired| wired.device().upcast()),
+			astal_network::Primary::Wifi => nw.wifi().map(|wifi| wifi.device().upcast()),
+			_ => None,
+		}
+	}
+
+	/// Finds NetworkManager's own object for an access point, which is what connecting needs.
+	fn nm_access_point(wifi: &Wifi, access_point: &AccessPoint) -> Option<libnm_api::AccessPoint> {
+		let bssid = access_point.bssid();
+		wifi.device()
+			.access_points()
+			.into_iter()
+			.find(|nm_access_point| nm_access_point.bssid() == bssid)
+	}
+
+	/// `key-mgmt` for a new profile with a password, `None` when the network needs none, or why we
+	/// can't join it.
+	fn key_mgmt(access_point: &libnm_api::AccessPoint) -> Result<Option<&'static str>, &'static str> {
+		use libnm_api::NM80211ApSecurityFlags as Security;
+
+		let security = access_point.wpa_flags() | access_point.rsn_flags();
+		if security.intersects(Security::KEY_MGMT_802_1X | Security::KEY_MGMT_EAP_SUITE_B_192) {
+			Err("Enterprise networks aren't supported")
+		} else if security.contains(Security::KEY_MGMT_PSK) {
+			// Also WPA3 transition networks, which take both
+			Ok(Some("wpa-psk"))
+		} else if security.contains(Security::KEY_MGMT_SAE) {
+			Ok(Some("sae"))
+		} else if security.intersects(Security::KEY_MGMT_OWE | Security::KEY_MGMT_OWE_TM) {
+			// Enhanced Open, encrypted but without a password
+			Ok(None)
+		} else if access_point.flags().contains(libnm_api::NM80211ApFlags::PRIVACY) {
+			Err("WEP networks aren't supported")
+		} else {
+			Ok(None)
+		}
+	}
+
+	fn saved_connection(wifi: &Wifi, access_point: &AccessPoint) -> Option<libnm_api::RemoteConnection> {
+		let nm_access_point = nm_access_point(wifi, access_point)?;
+		Network::default()?
+			.client()
+			.connections()
+			.into_iter()
+			.find(|connection| nm_access_point.connection_valid(connection))
+	}
+}
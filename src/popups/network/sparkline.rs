@@ -0,0 +1,107 @@
+use std::cell::RefCell;
+
+use glib::{Properties, clone};
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use gtk4::{gdk, graphene};
+
+use super::throughput::{HISTORY_LENGTH, Throughput};
+
+const WIDTH: i32 = 240;
+const HEIGHT: i32 = 48;
+/// Keeps an idle connection from scaling noise up to full height, in bytes per second
+const MIN_SCALE: f64 = 1_000.0;
+
+glib::wrapper! {
+	pub struct Sparkline(ObjectSubclass<imp::Sparkline>)
+		@extends gtk4::Widget,
+		@implements gtk4::Accessible, gtk4::Buildable, gtk4::ConstraintTarget;
+}
+
+impl Sparkline {
+	pub fn new(throughput: &Throughput) -> Self {
+		glib::Object::builder().property("throughput", throughput).build()
+	}
+}
+
+mod imp {
+	use super::*;
+
+	/// Download is drawn upwards from the middle, upload downwards.
+	#[derive(Default, Properties)]
+	#[properties(wrapper_type = super::Sparkline)]
+	pub struct Sparkline {
+		#[property(get, construct_only)]
+		throughput: RefCell<Option<Throughput>>,
+	}
+
+	#[glib::object_subclass]
+	impl ObjectSubclass for Sparkline {
+		type ParentType = gtk4::Widget;
+		type Type = super::Sparkline;
+
+		const NAME: &'static str = "Sparkline";
+
+		fn class_init(klass: &mut Self::Class) {
+			klass.set_css_name("sparkline");
+		}
+	}
+
+	#[glib::derived_properties]
+	impl ObjectImpl for Sparkline {
+		fn constructed(&self) {
+			self.parent_constructed();
+
+			let obj = self.obj();
+			if let Some(throughput) = &*self.throughput.borrow() {
+				// `transmitted` is notified last for every sample
+				throughput.connect_transmitted_notify(clone!(
+					#[weak]
+					obj,
+					move |_| obj.queue_draw()
+				));
+			}
+		}
+	}
+
+	impl WidgetImpl for Sparkline {
+		fn measure(&self, orientation: gtk4::Orientation, _for_size: i32) -> (i32, i32, i32, i32) {
+			let size = match orientation {
+				gtk4::Orientation::Horizontal => WIDTH,
+				_ => HEIGHT,
+			};
+			(size, size, -1, -1)
+		}
+
+		fn snapshot(&self, snapshot: &gtk4::Snapshot) {
+			let Some(throughput) = &*self.throughput.borrow() else {
+				return;
+			};
+
+			let obj = self.obj();
+			let width = obj.width() as f32;
+			let middle = obj.height() as f32 / 2.0;
+			let color = obj.color();
+			let faded = gdk::RGBA::new(color.red(), color.green(), color.blue(), color.alpha() * 0.5);
+
+			let history = throughput.history();
+			let scale = history
+				.iter()
+				.flat_map(|&(received, transmitted)| [received, transmitted])
+				.fold(MIN_SCALE, f64::max);
+
+			let bar_width = width / HISTORY_LENGTH as f32;
+			// Newest sample on the right edge
+			let offset = HISTORY_LENGTH - history.len();
+			for (index, (received, transmitted)) in history.into_iter().enumerate() {
+				let x = (offset + index) as f32 * bar_width;
+
+				let down = (received / scale) as f32 * middle;
+				snapshot.append_color(&color, &graphene::Rect::new(x, middle - down, bar_width, down));
+
+				let up = (transmitted / scale) as f32 * middle;
+				snapshot.append_color(&faded, &graphene::Rect::new(x, middle, bar_width, up));
+			}
+		}
+	}
+}
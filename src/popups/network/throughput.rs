@@ -0,0 +1,167 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use glib::{ControlFlow, Properties, clone};
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use sysinfo::Networks;
+
+/// Number of samples kept for the sparkline
+pub const HISTORY_LENGTH: usize = 60;
+
+#[derive(Clone, Copy, Default, Debug, clap::ValueEnum)]
+pub enum RateUnit {
+	#[default]
+	Bytes,
+	Bits,
+}
+
+glib::wrapper! {
+	pub struct Throughput(ObjectSubclass<imp::Throughput>);
+}
+
+impl Throughput {
+	/// The one sampler shared by every bar and popup, idle until [`Self::start`] is called.
+	pub fn default() -> Self {
+		thread_local! {
+			static THROUGHPUT: Throughput = glib::Object::builder().build();
+		}
+		THROUGHPUT.with(Clone::clone)
+	}
+
+	/// Starts sampling every `interval`, does nothing if it's already running.
+	pub fn start(&self, interval: Duration, unit: RateUnit) {
+		if self.imp().timer.borrow().is_none() {
+			self.imp().start(interval, unit);
+		}
+	}
+
+	/// `(received, transmitted)` in bytes per second, oldest first
+	pub fn history(&self) -> Vec<(f64, f64)> {
+		self.imp().history.borrow().iter().copied().collect()
+	}
+
+	/// Formats a rate in bytes per second using the configured unit, e.g. `1.2 MB/s` or `9.6 Mb/s`.
+	pub fn format_rate(&self, bytes_per_second: f64) -> String {
+		let (value, suffix) = match self.imp().unit.get() {
+			RateUnit::Bytes => (bytes_per_second, "B/s"),
+			RateUnit::Bits => (bytes_per_second * 8.0, "b/s"),
+		};
+
+		let (value, prefix) = [("G", 1e9), ("M", 1e6), ("k", 1e3)]
+			.into_iter()
+			.find(|(_, scale)| value >= *scale)
+			.map_or((value, ""), |(prefix, scale)| (value / scale, prefix));
+
+		if prefix.is_empty() || value >= 100.0 {
+			format!("{:.0} {}{}", value, prefix, suffix)
+		} else {
+			format!("{:.1} {}{}", value, prefix, suffix)
+		}
+	}
+}
+
+mod imp {
+	use super::*;
+
+	#[derive(Default, Properties)]
+	#[properties(wrapper_type = super::Throughput)]
+	pub struct Throughput {
+		/// Download rate over all interfaces in bytes per second
+		#[property(get)]
+		received:    Cell<f64>,
+		/// Upload rate over all interfaces in bytes per second
+		#[property(get)]
+		transmitted: Cell<f64>,
+
+		pub(super) unit:    Cell<RateUnit>,
+		pub(super) history: RefCell<VecDeque<(f64, f64)>>,
+		networks:           RefCell<Option<Networks>>,
+		last_refresh:       Cell<Option<Instant>>,
+		timer:              RefCell<Option<glib::SourceId>>,
+	}
+
+	#[glib::object_subclass]
+	impl ObjectSubclass for Throughput {
+		type Type = super::Throughput;
+
+		const NAME: &'static str = "Throughput";
+	}
+
+	#[glib::derived_properties]
+	impl ObjectImpl for Throughput {
+		fn dispose(&self) {
+			if let Some(timer) = self.timer.take() {
+				timer.remove();
+			}
+		}
+	}
+
+	impl Throughput {
+		pub(super) fn start(&self, interval: Duration, unit: RateUnit) {
+			self.unit.set(unit);
+			self.networks.replace(Some(Networks::new_with_refreshed_list()));
+			self.last_refresh.set(Some(Instant::now()));
+
+			let obj = self.obj();
+			let timer = glib::timeout_add_local(
+				interval,
+				clone!(
+					#[weak]
+					obj,
+					#[upgrade_or]
+					ControlFlow::Break,
+					move || {
+						obj.imp().sample();
+						ControlFlow::Continue
+					}
+				),
+			);
+			self.timer.replace(Some(timer));
+		}
+
+		fn sample(&self) {
+			let now = Instant::now();
+			let elapsed = self
+				.last_refresh
+				.replace(Some(now))
+				.map_or(0.0, |last| now.duration_since(last).as_secs_f64());
+			if elapsed <= 0.0 {
+				return;
+			}
+
+			let (received, transmitted) = {
+				let mut networks = self.networks.borrow_mut();
+				let Some(networks) = networks.as_mut() else {
+					return;
+				};
+				// Counters are per refresh, so this is what moved since the last sample
+				networks.refresh(true);
+				networks
+					.iter()
+					.filter(|(name, _)| name.as_str() != "lo")
+					.fold((0, 0), |(rx, tx), (_, data)| {
+						(rx + data.received(), tx + data.transmitted())
+					})
+			};
+
+			let received = received as f64 / elapsed;
+			let transmitted = transmitted as f64 / elapsed;
+
+			{
+				let mut history = self.history.borrow_mut();
+				history.push_back((received, transmitted));
+				while history.len() > HISTORY_LENGTH {
+					history.pop_front();
+				}
+			}
+
+			self.received.set(received);
+			self.transmitted.set(transmitted);
+			let obj = self.obj();
+			obj.notify_received();
+			obj.notify_transmitted();
+		}
+	}
+}
@@ -0,0 +1,85 @@
+use glib::clone;
+use libnm_api::prelude::*;
+use libnm_api::{ActiveConnection, Client, RemoteConnection};
+
+/// Connection types treated as VPN. WireGuard isn't a VPN plugin to NetworkManager, but it is to us.
+const VPN_TYPES: &[&str] = &["vpn", "wireguard"];
+
+pub fn active_vpns(client: &Client) -> Vec<ActiveConnection> {
+	client
+		.active_connections()
+		.into_iter()
+		.filter(|connection| VPN_TYPES.contains(&connection.connection_type().as_str()))
+		.collect()
+}
+
+/// Configured VPN profiles, sorted by name.
+pub fn vpn_profiles(client: &Client) -> Vec<RemoteConnection> {
+	let mut profiles = client
+		.connections()
+		.into_iter()
+		.filter(|connection| VPN_TYPES.contains(&connection.connection_type().as_str()))
+		.collect::<Vec<_>>();
+	profiles.sort_by_key(|profile| profile.id().to_lowercase());
+	profiles
+}
+
+/// Row with the profile name and a switch that (de)activates it.
+pub fn vpn_row(client: &Client, profile: &RemoteConnection, active: Option<ActiveConnection>) -> gtk4::ListBoxRow {
+	let label = gtk4::Label::builder()
+		.label(profile.id())
+		.hexpand(true)
+		.halign(gtk4::Align::Start)
+		.ellipsize(gtk4::pango::EllipsizeMode::End)
+		.build();
+
+	let switch = gtk4::Switch::builder()
+		.active(active.is_some())
+		.valign(gtk4::Align::Center)
+		.build();
+
+	let client = client.clone();
+	let profile = profile.clone();
+	switch.connect_state_set(move |switch, enable| {
+		let client = client.clone();
+		let profile = profile.clone();
+		let active = active.clone();
+
+		glib::spawn_future_local(clone!(
+			#[weak]
+			switch,
+			async move {
+				let result = match (enable, active) {
+					(true, None) => client
+						.activate_connection_future(Some(&profile), None::<&libnm_api::Device>, None)
+						.await
+						.map(|_| ()),
+					(false, Some(active)) => client.deactivate_connection_future(&active).await,
+					_ => Ok(()),
+				};
+
+				if let Err(e) = result {
+					eprintln!("Failed to toggle VPN {:?}: {}", profile.id(), e);
+					switch.set_active(!enable);
+					switch.set_state(!enable);
+				}
+			}
+		));
+
+		// The state follows once NetworkManager reports it and the list is rebuilt
+		glib::Propagation::Stop
+	});
+
+	let content = gtk4::Box::builder()
+		.orientation(gtk4::Orientation::Horizontal)
+		.spacing(8)
+		.build();
+	content.append(&label);
+	content.append(&switch);
+
+	gtk4::ListBoxRow::builder()
+		.child(&content)
+		.activatable(false)
+		.css_classes(["network-vpn"])
+		.build()
+}
@@ -1,26 +1,41 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::sync::OnceLock;
 
 use astal_wp::prelude::*;
-use astal_wp::{Direction, Endpoint};
-use glib::{GString, Properties};
+use astal_wp::{Device, Direction, Endpoint, MediaClass};
+use glib::subclass::Signal;
+use glib::{GString, Properties, clone};
 use gtk4::CompositeTemplate;
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
 
+use super::mark_local_change;
 use crate::icons::Icon;
 
 glib::wrapper! {
 	pub struct DeviceWidget(ObjectSubclass<imp::DeviceWidget>)
-		@extends gtk4::ToggleButton, gtk4::Button, gtk4::Widget,
-		@implements gtk4::Accessible, gtk4::Actionable, gtk4::Buildable, gtk4::Constraint, gtk4::ConstraintTarget;
+		@extends gtk4::Box, gtk4::Widget,
+		@implements gtk4::Accessible, gtk4::Buildable, gtk4::Constraint, gtk4::ConstraintTarget, gtk4::Orientable;
 }
 
 impl DeviceWidget {
 	pub fn new(device: &Endpoint) -> Self {
 		glib::Object::builder()
 			.property("endpoint", Some(device.clone()))
+			.property("expand-icon", Icon::ChevronDown.name())
 			.build()
 	}
+
+	/// Fires when the endpoint changes in a way that moves its row in the list.
+	pub fn connect_sort_changed<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+		self.connect_local("sort-changed", false, move |values| {
+			let obj = values[0]
+				.get::<Self>()
+				.expect("sort-changed emitted by a non-DeviceWidget");
+			f(&obj);
+			None
+		})
+	}
 }
 
 mod imp {
@@ -33,11 +48,39 @@ mod imp {
 	pub struct DeviceWidget {
 		#[property(get, construct_only)]
 		pub endpoint: RefCell<Option<Endpoint>>,
+
+		#[property(get, set)]
+		expand_icon:  RefCell<String>,
+		/// Only cards (ALSA, Bluetooth) with more than one profile get the profile dropdown
+		#[property(get, set)]
+		has_profiles: RefCell<bool>,
+		#[property(get, set)]
+		has_routes:   RefCell<bool>,
+
+		#[template_child]
+		default_button:   TemplateChild<gtk4::ToggleButton>,
+		#[template_child]
+		mute_button:      TemplateChild<gtk4::ToggleButton>,
+		#[template_child]
+		volume_slider:    TemplateChild<astal4::Slider>,
+		#[template_child]
+		profile_dropdown: TemplateChild<gtk4::DropDown>,
+		#[template_child]
+		route_dropdown:   TemplateChild<gtk4::DropDown>,
+
+		/// `is-default` and `description` watchers, which affect where the row sorts
+		endpoint_handlers: RefCell<Vec<glib::SignalHandlerId>>,
+
+		device:      RefCell<Option<Device>>,
+		profile_ids: RefCell<Vec<i32>>,
+		route_ids:   RefCell<Vec<i32>>,
+		/// Set while we fill the dropdowns ourselves, so it isn't mistaken for a user selection
+		syncing:     Cell<bool>,
 	}
 
 	#[glib::object_subclass]
 	impl ObjectSubclass for DeviceWidget {
-		type ParentType = gtk4::ToggleButton;
+		type ParentType = gtk4::Box;
 		type Type = super::DeviceWidget;
 
 		const NAME: &'static str = "DeviceWidget";
@@ -64,23 +107,117 @@ mod imp {
 			};
 			let obj = self.obj();
 
-			println!(
-				"DeviceWidget created for: {:?} (is-default: {})",
-				endpoint.description(),
-				endpoint.is_default()
-			);
+			// Keeps the OSD quiet while the user works our own controls, bound updates don't emit these
+			self.volume_slider.connect_change_value(|_, _, _| {
+				mark_local_change();
+				glib::Propagation::Proceed
+			});
+			self.mute_button.connect_clicked(|_| mark_local_change());
 
+			// Owned by the row so they go away with it
+			let handlers = ["is-default", "description"].map(|property| {
+				endpoint.connect_notify_local(
+					Some(property),
+					clone!(
+						#[weak]
+						obj,
+						move |_, _| obj.emit_by_name::<()>("sort-changed", &[])
+					),
+				)
+			});
+			self.endpoint_handlers.replace(handlers.into());
+
+			endpoint
+				.bind_property("is-default", &*self.default_button, "active")
+				.bidirectional()
+				.sync_create()
+				.build();
 			endpoint
-				.bind_property("is-default", &*obj, "active")
+				.bind_property("volume", &*self.volume_slider, "value")
 				.bidirectional()
 				.sync_create()
 				.build();
+			endpoint
+				.bind_property("mute", &*self.mute_button, "active")
+				.bidirectional()
+				.sync_create()
+				.build();
+
+			// The device shows up asynchronously, so follow it instead of reading it once
+			self.watch_device(endpoint.device());
+			endpoint.connect_notify_local(
+				Some("device"),
+				clone!(
+					#[weak]
+					obj,
+					move |endpoint, _| obj.imp().watch_device(endpoint.device())
+				),
+			);
+
+			self.refresh_routes();
+			for property in ["routes", "route-id"] {
+				endpoint.connect_notify_local(
+					Some(property),
+					clone!(
+						#[weak]
+						obj,
+						move |_, _| obj.imp().refresh_routes()
+					),
+				);
+			}
+
+			self.profile_dropdown.connect_selected_notify(clone!(
+				#[weak]
+				obj,
+				move |dropdown| {
+					let imp = obj.imp();
+					if imp.syncing.get() {
+						return;
+					}
+
+					let Some(&id) = imp.profile_ids.borrow().get(dropdown.selected() as usize) else {
+						return;
+					};
+					if let Some(device) = &*imp.device.borrow() {
+						device.set_active_profile_id(id);
+					}
+				}
+			));
+			self.route_dropdown.connect_selected_notify(clone!(
+				#[weak]
+				obj,
+				move |dropdown| {
+					let imp = obj.imp();
+					if imp.syncing.get() {
+						return;
+					}
+
+					let Some(&id) = imp.route_ids.borrow().get(dropdown.selected() as usize) else {
+						return;
+					};
+					if let Some(endpoint) = &*imp.endpoint.borrow() {
+						endpoint.set_route_id(id as u32);
+					}
+				}
+			));
+		}
+
+		fn signals() -> &'static [Signal] {
+			static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+			SIGNALS.get_or_init(|| vec![Signal::builder("sort-changed").build()])
+		}
+
+		fn dispose(&self) {
+			if let Some(endpoint) = &*self.endpoint.borrow() {
+				for handler in self.endpoint_handlers.take() {
+					endpoint.disconnect(handler);
+				}
+			}
 		}
 	}
 
 	impl WidgetImpl for DeviceWidget {}
-	impl ButtonImpl for DeviceWidget {}
-	impl ToggleButtonImpl for DeviceWidget {}
+	impl BoxImpl for DeviceWidget {}
 
 	#[gtk4::template_callbacks]
 	impl DeviceWidget {
@@ -92,5 +229,81 @@ mod imp {
 				_ => Icon::VolumeOff.name().into(),
 			}
 		}
+
+		#[template_callback]
+		fn mute_icon(&self, media_class: MediaClass, muted: bool) -> GString {
+			match (media_class, muted) {
+				(MediaClass::AudioSource, true) => Icon::MicOff.name().into(),
+				(MediaClass::AudioSource, false) => Icon::Mic.name().into(),
+				(_, true) => Icon::VolumeOff.name().into(),
+				(_, false) => Icon::Volume2.name().into(),
+			}
+		}
+	}
+
+	impl DeviceWidget {
+		fn watch_device(&self, device: Option<Device>) {
+			if let Some(device) = &device {
+				let obj = self.obj();
+				for property in ["profiles", "active-profile-id"] {
+					device.connect_notify_local(
+						Some(property),
+						clone!(
+							#[weak]
+							obj,
+							move |device, _| {
+								// Ignore a device we've already moved away from
+								let imp = obj.imp();
+								if imp.device.borrow().as_ref() == Some(device) {
+									imp.refresh_profiles();
+								}
+							}
+						),
+					);
+				}
+			}
+
+			self.device.replace(device);
+			self.refresh_profiles();
+		}
+
+		fn refresh_profiles(&self) {
+			let device = self.device.borrow();
+			let profiles = device.as_ref().map(|device| device.profiles()).unwrap_or_default();
+
+			let ids = profiles.iter().map(|profile| profile.index()).collect::<Vec<_>>();
+			let active = device
+				.as_ref()
+				.and_then(|device| ids.iter().position(|&id| id == device.active_profile_id()));
+			let names = profiles.iter().map(|profile| profile.description()).collect::<Vec<_>>();
+
+			self.obj().set_has_profiles(profiles.len() > 1);
+			self.profile_ids.replace(ids);
+			self.fill_dropdown(&self.profile_dropdown, &names, active);
+		}
+
+		fn refresh_routes(&self) {
+			let Some(endpoint) = &*self.endpoint.borrow() else {
+				return;
+			};
+
+			let routes = endpoint.routes();
+			let ids = routes.iter().map(|route| route.index()).collect::<Vec<_>>();
+			let active = ids.iter().position(|&id| id == endpoint.route_id() as i32);
+			let names = routes.iter().map(|route| route.description()).collect::<Vec<_>>();
+
+			self.obj().set_has_routes(routes.len() > 1);
+			self.route_ids.replace(ids);
+			self.fill_dropdown(&self.route_dropdown, &names, active);
+		}
+
+		fn fill_dropdown(&self, dropdown: &gtk4::DropDown, names: &[GString], active: Option<usize>) {
+			let names = names.iter().map(GString::as_str).collect::<Vec<_>>();
+
+			self.syncing.set(true);
+			dropdown.set_model(Some(&gtk4::StringList::new(&names)));
+			dropdown.set_selected(active.map_or(gtk4::INVALID_LIST_POSITION, |i| i as u32));
+			self.syncing.set(false);
+		}
 	}
 }
@@ -0,0 +1,126 @@
+use crate::icons::Icon;
+
+/// What the bar, popup and OSD show for the default speaker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeakerIndicator {
+	pub icon:    Icon,
+	pub label:   String,
+	/// Volume is above 100%, i.e. over-amplified
+	pub boosted: bool,
+}
+
+impl SpeakerIndicator {
+	/// `volume` is `None` when there is no default sink at all.
+	pub fn new(volume: Option<f64>, muted: bool) -> Self {
+		let Some(volume) = volume else {
+			return Self {
+				icon:    Icon::VolumeOff,
+				label:   "No output".to_string(),
+				boosted: false,
+			};
+		};
+
+		// Decide on the rounded percentage so icon and label can't disagree
+		let percentage = (volume.max(0.0) * 100.0).round() as u32;
+		let icon = match percentage {
+			_ if muted => Icon::VolumeOff,
+			0 => Icon::VolumeX,
+			1..=20 => Icon::Volume,
+			21..=70 => Icon::Volume1,
+			_ => Icon::Volume2,
+		};
+
+		Self {
+			icon,
+			label: format!("{}%", percentage),
+			boosted: percentage > 100,
+		}
+	}
+}
+
+/// Picks headphones, a display (HDMI/DisplayPort) or plain speakers from a sink's icon name and the
+/// name of its active route, e.g. `audio-headphones` or `hdmi-output-0`.
+pub fn sink_icon(icon_name: &str, route: &str) -> Icon {
+	let icon_name = icon_name.to_lowercase();
+	let route = route.to_lowercase();
+	let mentions = |needles: &[&str]| {
+		needles
+			.iter()
+			.any(|needle| icon_name.contains(needle) || route.contains(needle))
+	};
+
+	if mentions(&["headphone", "headset"]) {
+		Icon::Headphones
+	} else if mentions(&["hdmi", "displayport", "video-display"]) {
+		Icon::MonitorSpeaker
+	} else {
+		Icon::Speaker
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn icon(volume: f64) -> Icon {
+		SpeakerIndicator::new(Some(volume), false).icon
+	}
+
+	#[test]
+	fn missing_sink() {
+		let indicator = SpeakerIndicator::new(None, false);
+		assert_eq!(indicator.icon, Icon::VolumeOff);
+		assert_eq!(indicator.label, "No output");
+		assert!(!indicator.boosted);
+	}
+
+	#[test]
+	fn muted_keeps_percentage() {
+		let indicator = SpeakerIndicator::new(Some(0.5), true);
+		assert_eq!(indicator.icon, Icon::VolumeOff);
+		assert_eq!(indicator.label, "50%");
+
+		assert_eq!(SpeakerIndicator::new(Some(0.0), true).icon, Icon::VolumeOff);
+	}
+
+	#[test]
+	fn silent() {
+		assert_eq!(icon(0.0), Icon::VolumeX);
+		assert_eq!(icon(-0.1), Icon::VolumeX);
+		// Rounds down to 0%, so it must not show a speaker with waves
+		assert_eq!(icon(0.004), Icon::VolumeX);
+	}
+
+	#[test]
+	fn range_boundaries() {
+		assert_eq!(icon(0.01), Icon::Volume);
+		assert_eq!(icon(0.2), Icon::Volume);
+		assert_eq!(icon(0.21), Icon::Volume1);
+		assert_eq!(icon(0.7), Icon::Volume1);
+		assert_eq!(icon(0.71), Icon::Volume2);
+		assert_eq!(icon(1.0), Icon::Volume2);
+	}
+
+	#[test]
+	fn boost() {
+		let full = SpeakerIndicator::new(Some(1.0), false);
+		assert_eq!(full.label, "100%");
+		assert!(!full.boosted);
+
+		let boosted = SpeakerIndicator::new(Some(1.5), false);
+		assert_eq!(boosted.icon, Icon::Volume2);
+		assert_eq!(boosted.label, "150%");
+		assert!(boosted.boosted);
+
+		assert!(SpeakerIndicator::new(Some(1.01), false).boosted);
+	}
+
+	#[test]
+	fn sink_kinds() {
+		assert_eq!(sink_icon("audio-headphones", ""), Icon::Headphones);
+		assert_eq!(sink_icon("audio-card", "analog-output-headphones"), Icon::Headphones);
+		assert_eq!(sink_icon("audio-card", "hdmi-output-0"), Icon::MonitorSpeaker);
+		assert_eq!(sink_icon("audio-card", "analog-output-speaker"), Icon::Speaker);
+		assert_eq!(sink_icon("", ""), Icon::Speaker);
+	}
+}
@@ -1,18 +1,41 @@
 mod device;
+mod indicator;
+mod stream;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use astal_wp::prelude::NodeExt;
-use astal_wp::{Audio, Endpoint, MediaClass, Node, Wp};
+use astal_wp::{Audio, Endpoint, MediaClass, Node, Stream, Wp};
 use device::DeviceWidget;
 use glib::{GString, Properties, clone};
 use gtk4::CompositeTemplate;
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
+pub use indicator::{SpeakerIndicator, sink_icon};
+use stream::StreamWidget;
 
 use crate::icons::Icon;
 
+/// How long after a change from one of our own controls the OSD stays quiet
+const LOCAL_CHANGE_WINDOW: Duration = Duration::from_millis(300);
+
+thread_local! {
+	static LOCAL_CHANGE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Marks a volume or mute change as coming from the popup's own controls.
+fn mark_local_change() {
+	LOCAL_CHANGE.set(Some(Instant::now()));
+}
+
+/// Whether the volume change being handled came from the popup's own controls
+pub fn is_local_change() -> bool {
+	LOCAL_CHANGE.get().is_some_and(|at| at.elapsed() < LOCAL_CHANGE_WINDOW)
+}
+
 glib::wrapper! {
 	pub struct VolumePopup(ObjectSubclass<imp::VolumePopup>)
 		@extends gtk4::Popover, gtk4::Widget,
@@ -28,6 +51,75 @@ impl VolumePopup {
 	}
 }
 
+/// The endpoint `property` (`default-speaker` or `default-microphone`) of [`Audio`] points to.
+/// Unlike `Audio::default_speaker` this is `None` when there is none, e.g. with no devices at all.
+pub fn default_endpoint(audio: &Audio, property: &str) -> Option<Endpoint> {
+	audio.property::<Option<Endpoint>>(property)
+}
+
+pub fn speaker_indicator(speaker: Option<&Endpoint>) -> SpeakerIndicator {
+	SpeakerIndicator::new(speaker.map(|s| s.volume()), speaker.is_some_and(|s| s.is_muted()))
+}
+
+/// Calls `f` with the default speaker now and whenever it, or its volume, mute, icon or route,
+/// changes.
+pub fn watch_default_speaker(audio: &Audio, f: impl Fn(Option<&Endpoint>) + 'static) {
+	let f = Rc::new(f);
+	// Handlers on the current default speaker, replaced whenever it changes
+	let current = Rc::new(RefCell::new(None::<(Endpoint, Vec<glib::SignalHandlerId>)>));
+
+	let changed_default_speaker = move |audio: &Audio| {
+		if let Some((speaker, handlers)) = current.borrow_mut().take() {
+			for handler in handlers {
+				speaker.disconnect(handler);
+			}
+		}
+
+		let speaker = default_endpoint(audio, "default-speaker");
+		f(speaker.as_ref());
+
+		if let Some(speaker) = speaker {
+			let handlers = ["volume", "mute", "icon", "route"]
+				.into_iter()
+				.map(|property| {
+					let f = f.clone();
+					speaker.connect_notify_local(Some(property), move |speaker, _| f(Some(speaker)))
+				})
+				.collect();
+			*current.borrow_mut() = Some((speaker, handlers));
+		}
+	};
+
+	changed_default_speaker(audio);
+	audio.connect_notify_local(Some("default-speaker"), move |audio, _| changed_default_speaker(audio));
+}
+
+/// Makes the next output (by node id) the default one, skipping outputs whose description contains
+/// one of `exclude`.
+pub fn cycle_output(exclude: &[String]) {
+	let audio = Wp::default().audio();
+	let mut speakers = audio
+		.speakers()
+		.into_iter()
+		.filter(|speaker| {
+			let description = speaker.description();
+			!exclude
+				.iter()
+				.any(|pattern| description.as_ref().is_some_and(|d| d.contains(pattern.as_str())))
+		})
+		.collect::<Vec<_>>();
+	speakers.sort_by_key(|speaker| speaker.id());
+
+	let current = default_endpoint(&audio, "default-speaker").map(|speaker| speaker.id());
+	let next = speakers
+		.iter()
+		.find(|speaker| current.is_some_and(|current| speaker.id() > current))
+		.or(speakers.first());
+	if let Some(next) = next {
+		next.set_is_default(true);
+	}
+}
+
 mod imp {
 	use super::*;
 
@@ -49,8 +141,16 @@ mod imp {
 		#[property(get, set)]
 		mic_volume_percentage: RefCell<u8>,
 
+		#[property(get, set)]
+		has_streams: RefCell<bool>,
+
 		#[template_child]
 		devices_list_view: gtk4::TemplateChild<gtk4::ListView>,
+		#[template_child]
+		streams_box:       gtk4::TemplateChild<gtk4::Box>,
+
+		/// Application playback streams by node id
+		streams: RefCell<HashMap<u32, StreamWidget>>,
 	}
 
 	#[glib::object_subclass]
@@ -102,31 +202,45 @@ mod imp {
 			let factory = Self::create_device_factory();
 			let header_factory = Self::create_header_factory();
 
-			// Create sorter to group by device direction
+			// Sections: group by device direction (Output before Input)
+			let section_sorter = gtk4::CustomSorter::new(move |a, b| {
+				let a_dir = a
+					.downcast_ref::<DeviceWidget>()
+					.unwrap()
+					.endpoint()
+					.map(|ep| ep.media_class());
+				let b_dir = b
+					.downcast_ref::<DeviceWidget>()
+					.unwrap()
+					.endpoint()
+					.map(|ep| ep.media_class());
+
+				b_dir.cmp(&a_dir).into()
+			});
+
+			// Within a section the default device comes first, the rest alphabetically
 			let sorter = gtk4::CustomSorter::new(move |a, b| {
 				let a = a.downcast_ref::<DeviceWidget>().unwrap();
 				let b = b.downcast_ref::<DeviceWidget>().unwrap();
 
-				let a_endpoint = a.endpoint();
-				let b_endpoint = b.endpoint();
-
-				if let (Some(a_ep), Some(b_ep)) = (a_endpoint, b_endpoint) {
-					// Sort by direction first (Output before Input)
-					let a_dir = a_ep.media_class();
-					let b_dir = b_ep.media_class();
-
-					if a_dir != b_dir {
-						return b_dir.cmp(&a_dir).into();
-					}
-				}
-
-				gtk4::Ordering::Equal
+				let (Some(a_ep), Some(b_ep)) = (a.endpoint(), b.endpoint()) else {
+					return gtk4::Ordering::Equal;
+				};
+
+				b_ep.media_class()
+					.cmp(&a_ep.media_class())
+					.then_with(|| b_ep.is_default().cmp(&a_ep.is_default()))
+					.then_with(|| {
+						let a_description = a_ep.description().unwrap_or_default().to_lowercase();
+						let b_description = b_ep.description().unwrap_or_default().to_lowercase();
+						a_description.cmp(&b_description)
+					})
+					.then_with(|| a_ep.id().cmp(&b_ep.id()))
+					.into()
 			});
 
 			let sort_model = gtk4::SortListModel::new(Some(store.clone()), Some(sorter.clone()));
-
-			// Use the same sorter for section sorter
-			sort_model.set_section_sorter(Some(&sorter));
+			sort_model.set_section_sorter(Some(&section_sorter));
 
 			let selection_model = gtk4::NoSelection::new(Some(sort_model));
 
@@ -134,32 +248,52 @@ mod imp {
 			self.devices_list_view.set_factory(Some(&factory));
 			self.devices_list_view.set_header_factory(Some(&header_factory));
 
-			// Populate devices from wp.nodes()
+			// Sync devices from wp.nodes(), keyed by node id so unchanged devices keep their widget
 			let populate_devices = clone!(
 				#[weak]
 				store,
+				#[weak]
+				sorter,
 				move |wp: &Wp| {
-					// Clear existing
-					store.remove_all();
-
-					// Add all audio nodes
-					for node in wp.nodes().iter() {
-						let media_class = node.media_class();
-
-						// Filter for audio sink (speakers) and source (microphones) nodes only
-						if matches!(media_class, MediaClass::AudioSink | MediaClass::AudioSource) {
-							// Node IS an Endpoint, just downcast
-							if let Ok(endpoint) = node.clone().downcast::<Endpoint>() {
-								let widget = DeviceWidget::new(&endpoint);
-								store.append(&widget);
-							}
-						}
+					// Filter for audio sink (speakers) and source (microphones) nodes only.
+					// Those nodes ARE Endpoints, just downcast
+					let endpoints = wp
+						.nodes()
+						.into_iter()
+						.filter(|node| matches!(node.media_class(), MediaClass::AudioSink | MediaClass::AudioSource))
+						.filter_map(|node| node.downcast::<Endpoint>().ok())
+						.collect::<Vec<_>>();
+					let ids = endpoints.iter().map(|ep| ep.id()).collect::<HashSet<_>>();
+
+					store.retain(|item| {
+						item.downcast_ref::<DeviceWidget>()
+							.and_then(|widget| widget.endpoint())
+							.is_some_and(|ep| ids.contains(&ep.id()))
+					});
+
+					let existing = store
+						.iter::<DeviceWidget>()
+						.filter_map(Result::ok)
+						.filter_map(|widget| widget.endpoint())
+						.map(|ep| ep.id())
+						.collect::<HashSet<_>>();
+
+					for endpoint in endpoints.into_iter().filter(|ep| !existing.contains(&ep.id())) {
+						let widget = DeviceWidget::new(&endpoint);
+						widget.connect_sort_changed(clone!(
+							#[weak]
+							sorter,
+							move |_| sorter.changed(gtk4::SorterChange::Different)
+						));
+						store.append(&widget);
 					}
 				}
 			);
 
 			populate_devices(&wp);
 			wp.connect_nodes_notify(populate_devices);
+
+			self.bind_streams(&audio);
 		}
 	}
 
@@ -176,6 +310,7 @@ mod imp {
 		#[template_callback]
 		fn on_toggle_speaker_mute(&self) {
 			if let Some(speaker) = &*self.default_speaker.borrow() {
+				mark_local_change();
 				speaker.set_mute(!speaker.is_muted());
 			}
 		}
@@ -183,6 +318,7 @@ mod imp {
 		#[template_callback]
 		fn on_toggle_mic_mute(&self) {
 			if let Some(mic) = &*self.default_mic.borrow() {
+				mark_local_change();
 				mic.set_mute(!mic.is_muted());
 			}
 		}
@@ -251,33 +387,58 @@ mod imp {
 			factory
 		}
 
-		fn bind_speaker_icon(&self, audio: &Audio) {
+		fn bind_streams(&self, audio: &Audio) {
 			let obj = self.obj();
 
-			let default_speaker =
-				gtk4::PropertyExpression::new(Audio::static_type(), gtk4::Expression::NONE, "default-speaker");
-			let default_speaker_mute =
-				gtk4::PropertyExpression::new(Node::static_type(), Some(default_speaker.clone()), "mute");
-			let default_speaker_volume =
-				gtk4::PropertyExpression::new(Node::static_type(), Some(default_speaker), "volume");
+			for stream in audio.streams() {
+				self.add_stream(&stream);
+			}
 
-			let speaker_icon = gtk4::ClosureExpression::new::<GString>(
-				[&default_speaker_mute, &default_speaker_volume],
-				glib::closure!(move |_: &glib::Object, is_muted: bool, volume: f64| -> GString {
-					if is_muted {
-						Icon::VolumeOff.name().into()
-					} else {
-						match volume {
-							0.0 => Icon::VolumeX.name().into(),
-							0.0..=0.2 => Icon::Volume.name().into(),
-							0.2..=0.7 => Icon::Volume1.name().into(),
-							0.7..=1.0 => Icon::Volume2.name().into(),
-							_ => Icon::Volume2.name().into(),
-						}
+			audio.connect_stream_added(clone!(
+				#[weak]
+				obj,
+				move |_, stream| obj.imp().add_stream(stream)
+			));
+			audio.connect_stream_removed(clone!(
+				#[weak]
+				obj,
+				move |_, stream| {
+					let imp = obj.imp();
+					if let Some(widget) = imp.streams.borrow_mut().remove(&stream.id()) {
+						imp.streams_box.remove(&widget);
 					}
-				}),
+					obj.set_has_streams(!imp.streams.borrow().is_empty());
+				}
+			));
+		}
+
+		fn add_stream(&self, stream: &Stream) {
+			// Filter out other audio streams (e.g. recorders) just in case
+			if stream.media_class() != MediaClass::AudioStream {
+				return;
+			}
+
+			let mut streams = self.streams.borrow_mut();
+			if streams.contains_key(&stream.id()) {
+				return;
+			}
+
+			let widget = StreamWidget::new(stream);
+			self.streams_box.append(&widget);
+			streams.insert(stream.id(), widget);
+			self.obj().set_has_streams(true);
+		}
+
+		fn bind_speaker_icon(&self, audio: &Audio) {
+			let obj = self.obj();
+			watch_default_speaker(
+				audio,
+				clone!(
+					#[weak]
+					obj,
+					move |speaker| obj.set_speaker_icon(speaker_indicator(speaker).icon.name())
+				),
 			);
-			speaker_icon.bind(&*obj, "speaker-icon", Some(audio));
 		}
 
 		fn bind_speaker_volume(&self, audio: &Audio) {
@@ -294,11 +455,17 @@ mod imp {
 					if let Some(b) = speaker_binding.borrow_mut().take() {
 						b.unbind();
 					}
-					let speaker = audio.default_speaker();
+					let Some(speaker) = default_endpoint(audio, "default-speaker") else {
+						return;
+					};
 					let binding = speaker
 						.bind_property("volume", &obj, "speaker-volume-percentage")
 						.transform_to(|_, volume: f64| Some((volume * 100.0) as u8))
-						.transform_from(|_, volume_percentage: u8| Some((volume_percentage as f64) / 100.0))
+						// Only runs for changes made through our slider
+						.transform_from(|_, volume_percentage: u8| {
+							mark_local_change();
+							Some((volume_percentage as f64) / 100.0)
+						})
 						.bidirectional()
 						.build();
 					*speaker_binding.borrow_mut() = Some(binding);
@@ -342,11 +509,17 @@ mod imp {
 					if let Some(b) = mic_binding.borrow_mut().take() {
 						b.unbind();
 					}
-					let mic = audio.default_microphone();
+					let Some(mic) = default_endpoint(audio, "default-microphone") else {
+						return;
+					};
 					let binding = mic
 						.bind_property("volume", &obj, "mic-volume-percentage")
 						.transform_to(|_, volume: f64| Some((volume * 100.0) as u8))
-						.transform_from(|_, volume_percentage: u8| Some((volume_percentage as f64) / 100.0))
+						// Only runs for changes made through our slider
+						.transform_from(|_, volume_percentage: u8| {
+							mark_local_change();
+							Some((volume_percentage as f64) / 100.0)
+						})
 						.bidirectional()
 						.build();
 					*mic_binding.borrow_mut() = Some(binding);
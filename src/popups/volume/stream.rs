@@ -0,0 +1,197 @@
+use std::cell::{Cell, OnceCell, RefCell};
+
+use astal_wp::prelude::*;
+use astal_wp::{Endpoint, Stream, Wp};
+use glib::{GString, Properties, clone};
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use gtk4::{CompositeTemplate, gio};
+
+use crate::icons::Icon;
+
+glib::wrapper! {
+	pub struct StreamWidget(ObjectSubclass<imp::StreamWidget>)
+		@extends gtk4::Box, gtk4::Widget,
+		@implements gtk4::Accessible, gtk4::Buildable, gtk4::Constraint, gtk4::ConstraintTarget, gtk4::Orientable;
+}
+
+impl StreamWidget {
+	pub fn new(stream: &Stream) -> Self {
+		glib::Object::builder().property("stream", Some(stream.clone())).build()
+	}
+}
+
+mod imp {
+	use super::*;
+
+	#[derive(Default, Properties, CompositeTemplate)]
+	#[template(file = "./src/popups/volume/stream.blp")]
+	#[properties(wrapper_type = super::StreamWidget)]
+	pub struct StreamWidget {
+		#[property(get, construct_only)]
+		pub stream: RefCell<Option<Stream>>,
+
+		#[template_child]
+		mute_button:   TemplateChild<gtk4::ToggleButton>,
+		#[template_child]
+		volume_slider: TemplateChild<astal4::Slider>,
+		#[template_child]
+		sink_dropdown: TemplateChild<gtk4::DropDown>,
+
+		sinks:          OnceCell<gio::ListStore>,
+		/// Set while we update the dropdown ourselves, so it isn't mistaken for the user moving the stream
+		syncing_target: Cell<bool>,
+	}
+
+	#[glib::object_subclass]
+	impl ObjectSubclass for StreamWidget {
+		type ParentType = gtk4::Box;
+		type Type = super::StreamWidget;
+
+		const NAME: &'static str = "StreamWidget";
+
+		fn class_init(klass: &mut Self::Class) {
+			klass.bind_template();
+			klass.bind_template_callbacks();
+		}
+
+		fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+			obj.init_template();
+		}
+	}
+
+	#[glib::derived_properties]
+	impl ObjectImpl for StreamWidget {
+		fn constructed(&self) {
+			self.parent_constructed();
+
+			let stream = self.stream.borrow();
+			let Some(stream) = &*stream else {
+				return;
+			};
+			let obj = self.obj();
+
+			stream
+				.bind_property("volume", &*self.volume_slider, "value")
+				.bidirectional()
+				.sync_create()
+				.build();
+			stream
+				.bind_property("mute", &*self.mute_button, "active")
+				.bidirectional()
+				.sync_create()
+				.build();
+
+			self.sink_dropdown.set_model(Some(self.sinks()));
+			self.sink_dropdown.set_expression(Some(gtk4::PropertyExpression::new(
+				Endpoint::static_type(),
+				gtk4::Expression::NONE,
+				"description",
+			)));
+
+			let audio = Wp::default().audio();
+			self.refresh_sinks();
+			audio.connect_speakers_notify(clone!(
+				#[weak]
+				obj,
+				move |_| obj.imp().refresh_sinks()
+			));
+			stream.connect_notify_local(
+				Some("target-endpoint"),
+				clone!(
+					#[weak]
+					obj,
+					move |_, _| obj.imp().sync_selected_sink()
+				),
+			);
+
+			self.sink_dropdown.connect_selected_item_notify(clone!(
+				#[weak]
+				obj,
+				move |dropdown| {
+					let imp = obj.imp();
+					if imp.syncing_target.get() {
+						return;
+					}
+
+					let Some(sink) = dropdown.selected_item().and_downcast::<Endpoint>() else {
+						return;
+					};
+					if let Some(stream) = &*imp.stream.borrow() {
+						stream.set_target_endpoint(Some(&sink));
+					}
+				}
+			));
+		}
+	}
+
+	impl WidgetImpl for StreamWidget {}
+	impl BoxImpl for StreamWidget {}
+
+	#[gtk4::template_callbacks]
+	impl StreamWidget {
+		#[template_callback]
+		fn mute_icon(&self, muted: bool) -> &'static str {
+			if muted {
+				Icon::VolumeOff.name()
+			} else {
+				Icon::Volume2.name()
+			}
+		}
+
+		#[template_callback]
+		fn app_icon(&self, icon: Option<&str>) -> GString {
+			// Node icons are freedesktop icon names which may not exist in the theme, fall back to
+			// a generic one rather than showing a missing image
+			match icon {
+				Some(icon) if gtk4::IconTheme::for_display(&self.obj().display()).has_icon(icon) => icon.into(),
+				_ => "application-x-executable".into(),
+			}
+		}
+	}
+
+	impl StreamWidget {
+		fn sinks(&self) -> &gio::ListStore {
+			self.sinks.get_or_init(gio::ListStore::new::<Endpoint>)
+		}
+
+		fn refresh_sinks(&self) {
+			let sinks = self.sinks();
+
+			self.syncing_target.set(true);
+			sinks.remove_all();
+			for sink in Wp::default().audio().speakers() {
+				sinks.append(&sink);
+			}
+			self.syncing_target.set(false);
+
+			self.sync_selected_sink();
+		}
+
+		/// Selects the sink the stream is currently routed to, which is the default one unless
+		/// the stream was moved.
+		fn sync_selected_sink(&self) {
+			let Some(stream) = &*self.stream.borrow() else {
+				return;
+			};
+
+			let target = stream
+				.target_endpoint()
+				.or_else(|| super::default_endpoint(&Wp::default().audio(), "default-speaker"));
+
+			let sinks = self.sinks();
+			let position = (0..sinks.n_items())
+				.find(|&i| {
+					sinks
+						.item(i)
+						.and_downcast::<Endpoint>()
+						.is_some_and(|sink| target.as_ref().is_some_and(|target| sink.id() == target.id()))
+				})
+				.unwrap_or(gtk4::INVALID_LIST_POSITION);
+
+			self.syncing_target.set(true);
+			self.sink_dropdown.set_selected(position);
+			self.syncing_target.set(false);
+		}
+	}
+}